@@ -0,0 +1,706 @@
+//! The game camera: a single camera that follows a [`CameraTarget`]
+//! in one of several [`CameraMode`]s and can fly freely, plus the mouse:
+//! grabbing the cursor, looking around with it and aiming at the floor.
+
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use bevy::window::{CursorGrabMode, PrimaryWindow};
+use bevy_xpbd_3d::prelude::*;
+use smooth_bevy_cameras::{look_transform_system, LookTransform, LookTransformBundle, Smoother};
+
+use crate::character::{PlayerGamepad, PlayerId, PlayerInput};
+use crate::controls::{Action, ActionInput, ActionMap};
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraSettings>()
+            .init_resource::<CursorAim>()
+            .add_event::<MouseMotion>()
+            .add_event::<CycleCameraMode>()
+            .add_systems(Startup, spawn_camera)
+            .add_systems(
+                Update,
+                cycle_input
+                    .in_set(PlayerInput)
+                    .before(cycle_mode)
+                    .run_if(resource_exists::<ActionMap>()),
+            )
+            .add_systems(
+                Update,
+                (
+                    cycle_mode,
+                    grab_cursor.run_if(resource_exists::<ActionMap>()),
+                    mouse_look,
+                    stick_look.run_if(resource_exists::<ActionMap>()),
+                    fly.run_if(resource_exists::<ActionMap>()),
+                    follow_target,
+                    avoid_obstacles.run_if(resource_exists::<SpatialQueryPipeline>()),
+                )
+                    .chain()
+                    .before(look_transform_system),
+            )
+            .add_systems(Update, aim_cursor);
+    }
+}
+
+/// Sent to switch the camera to its next [`CameraMode`].
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CycleCameraMode;
+
+/// A marker component for the entity the camera follows.
+#[derive(Component)]
+pub struct CameraTarget;
+
+/// The camera and the mode it is in.
+#[derive(Component)]
+pub struct GameCamera {
+    pub mode: CameraMode,
+    /// Yaw and pitch, in radians, the mouse has turned the view by
+    /// relative to the target's facing.
+    pub orbit: Vec2,
+}
+
+/// The point on the floor under the mouse cursor. Only set while the cursor
+/// is free and the camera looks from above.
+#[derive(Resource, Default)]
+pub struct CursorAim {
+    pub point: Option<Vec3>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Looks down at the target from above.
+    TopDown,
+    /// Orbits behind the target as it turns.
+    ThirdPerson,
+    /// Looks through the eyes of the target.
+    FirstPerson,
+    /// Detached from the target and moved with its own keys.
+    FreeFly,
+}
+
+impl CameraMode {
+    /// Whether the mouse turns the view in this mode, which needs a grabbed cursor.
+    pub fn mouse_look(self) -> bool {
+        self != CameraMode::TopDown
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            | CameraMode::TopDown => CameraMode::ThirdPerson,
+            | CameraMode::ThirdPerson => CameraMode::FirstPerson,
+            | CameraMode::FirstPerson => CameraMode::FreeFly,
+            | CameraMode::FreeFly => CameraMode::TopDown,
+        }
+    }
+}
+
+/// Per-mode camera settings. `lag` is the [`Smoother`] lag weight,
+/// from zero (no smoothing) to just below one.
+#[derive(Resource)]
+pub struct CameraSettings {
+    /// Whether movement keys are relative to the camera, so that forward
+    /// moves away from it, rather than to the character's facing.
+    pub camera_relative: bool,
+    pub mouse: MouseSettings,
+    /// How many metres a shared camera backs off per metre the players
+    /// spread from their middle.
+    pub framing_margin: f32,
+    pub top_down: TopDownSettings,
+    pub third_person: ThirdPersonSettings,
+    pub first_person: FirstPersonSettings,
+    pub free_fly: FreeFlySettings,
+}
+
+pub struct MouseSettings {
+    /// Radians the view turns per pixel of mouse motion.
+    pub sensitivity: f32,
+    /// How far, in radians, the view can tilt up or down.
+    pub max_pitch: f32,
+    /// Radians per second the view tilts with [`Action::LookUp`] and
+    /// [`Action::LookDown`] fully held.
+    pub stick_speed: f32,
+}
+
+pub struct TopDownSettings {
+    pub height: f32,
+    /// How far the camera stays behind the target along +Z, so that walls
+    /// facing the camera are not seen exactly edge-on.
+    pub offset: f32,
+    pub lag: f32,
+}
+
+pub struct ThirdPersonSettings {
+    pub distance: f32,
+    pub height: f32,
+    pub lag: f32,
+    /// Radius of the sphere cast from the target to the camera: when it hits
+    /// the level, the camera moves in front of the obstacle.
+    pub collision_radius: f32,
+    /// The camera never comes closer to the target than this.
+    pub min_distance: f32,
+}
+
+pub struct FirstPersonSettings {
+    pub eye_height: f32,
+    pub lag: f32,
+}
+
+pub struct FreeFlySettings {
+    /// Speed in units per second.
+    pub speed: f32,
+    pub lag: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            camera_relative: true,
+            framing_margin: 1.5,
+            mouse: MouseSettings {
+                sensitivity: 0.003,
+                max_pitch: 1.2,
+                stick_speed: 2.0,
+            },
+            top_down: TopDownSettings {
+                height: 20.0,
+                offset: 6.0,
+                lag: 0.85,
+            },
+            third_person: ThirdPersonSettings {
+                distance: 6.0,
+                height: 4.0,
+                lag: 0.9,
+                collision_radius: 0.3,
+                min_distance: 1.0,
+            },
+            first_person: FirstPersonSettings {
+                eye_height: 0.6,
+                lag: 0.5,
+            },
+            free_fly: FreeFlySettings {
+                speed: 10.0,
+                lag: 0.8,
+            },
+        }
+    }
+}
+
+impl CameraSettings {
+    fn lag(&self, mode: CameraMode) -> f32 {
+        match mode {
+            | CameraMode::TopDown => self.top_down.lag,
+            | CameraMode::ThirdPerson => self.third_person.lag,
+            | CameraMode::FirstPerson => self.first_person.lag,
+            | CameraMode::FreeFly => self.free_fly.lag,
+        }
+    }
+
+    /// Where the camera should look from and at for a target at `target`,
+    /// turned by `orbit` (see [`GameCamera::orbit`]).
+    /// A free-flying camera does not follow the target.
+    pub fn look(&self, mode: CameraMode, orbit: Vec2, target: &Transform) -> Option<LookTransform> {
+        let position = target.translation;
+        let turn = Quat::from_rotation_y(orbit.x) * Quat::from_axis_angle(target.right(), orbit.y);
+        let forward = turn * target.forward();
+        let (eye, look_at) = match mode {
+            | CameraMode::TopDown => (
+                position + Vec3::new(0.0, self.top_down.height, self.top_down.offset),
+                position,
+            ),
+            | CameraMode::ThirdPerson => (
+                position
+                    + turn
+                        * (-target.forward() * self.third_person.distance
+                            + Vec3::Y * self.third_person.height),
+                position,
+            ),
+            | CameraMode::FirstPerson => {
+                let eye = position + Vec3::Y * self.first_person.eye_height;
+                (eye, eye + forward)
+            }
+            | CameraMode::FreeFly => return None,
+        };
+        Some(LookTransform::new(eye, look_at, Vec3::Y))
+    }
+}
+
+fn spawn_camera(mut commands: Commands, settings: Res<CameraSettings>) {
+    let mode = CameraMode::ThirdPerson;
+    commands.spawn((
+        GameCamera {
+            mode,
+            orbit: Vec2::ZERO,
+        },
+        LookTransformBundle {
+            transform: LookTransform::new(Vec3::new(0.0, 10.0, 10.0), Vec3::ZERO, Vec3::Y),
+            smoother: Smoother::new(settings.lag(mode)),
+        },
+        Camera3dBundle {
+            projection: PerspectiveProjection {
+                fov: 75.0_f32.to_radians(),
+                ..default()
+            }
+            .into(),
+            ..default()
+        },
+    ));
+}
+
+/// Sends [`CycleCameraMode`] on [`Action::CycleCamera`], from the keyboard
+/// or the gamepad of any player.
+fn cycle_input(
+    input: ActionInput,
+    gamepads: Query<&PlayerGamepad>,
+    mut cycle_event_writer: EventWriter<CycleCameraMode>,
+) {
+    let pressed = input.keyboard_just_pressed(Action::CycleCamera)
+        || gamepads.iter().any(|&PlayerGamepad(gamepad)| {
+            input.gamepad_just_pressed(gamepad, Action::CycleCamera)
+        });
+    if pressed {
+        cycle_event_writer.send(CycleCameraMode);
+    }
+}
+
+/// Switches the camera to the next mode on [`CycleCameraMode`].
+fn cycle_mode(
+    mut cycle_event_reader: EventReader<CycleCameraMode>,
+    settings: Res<CameraSettings>,
+    mut cameras: Query<(&mut GameCamera, &mut Smoother)>,
+    mut targets: Query<&mut Visibility, With<CameraTarget>>,
+) {
+    for _ in cycle_event_reader.read() {
+        for (mut camera, mut smoother) in &mut cameras {
+            camera.mode = camera.mode.next();
+            smoother.set_lag_weight(settings.lag(camera.mode));
+
+            // Looking through the target's eyes, its own model would be in the way
+            let target_visibility = if camera.mode == CameraMode::FirstPerson {
+                Visibility::Hidden
+            } else {
+                Visibility::Inherited
+            };
+            for mut visibility in &mut targets {
+                *visibility = target_visibility;
+            }
+        }
+    }
+}
+
+/// Grabs the cursor on [`Action::GrabCursor`] in modes with mouse look
+/// and releases it on [`Action::ReleaseCursor`] or in other modes.
+fn grab_cursor(
+    input: ActionInput,
+    cameras: Query<&GameCamera>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+    let mouse_look = cameras.iter().any(|camera| camera.mode.mouse_look());
+    let grabbed = window.cursor.grab_mode != CursorGrabMode::None;
+
+    let grab = if !mouse_look || input.keyboard_just_pressed(Action::ReleaseCursor) {
+        false
+    } else if input.keyboard_just_pressed(Action::GrabCursor) && window.focused {
+        true
+    } else {
+        grabbed && window.focused
+    };
+    if grab != grabbed {
+        window.cursor.grab_mode = if grab {
+            CursorGrabMode::Locked
+        } else {
+            CursorGrabMode::None
+        };
+        window.cursor.visible = !grab;
+    }
+}
+
+/// Turns the view with the mouse while the cursor is grabbed: orbits a following
+/// camera around the target and turns a free-flying one in place.
+fn mouse_look(
+    mut mouse_motion: EventReader<MouseMotion>,
+    settings: Res<CameraSettings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<(&mut GameCamera, &mut LookTransform)>,
+) {
+    let delta: Vec2 = mouse_motion.read().map(|motion| motion.delta).sum();
+    let grabbed = windows
+        .get_single()
+        .is_ok_and(|window| window.cursor.grab_mode != CursorGrabMode::None);
+    if !grabbed || delta == Vec2::ZERO {
+        return;
+    }
+    let turn = -delta * settings.mouse.sensitivity;
+
+    for (mut camera, mut look) in &mut cameras {
+        match camera.mode {
+            | CameraMode::TopDown => {}
+            | CameraMode::ThirdPerson | CameraMode::FirstPerson => {
+                let max_pitch = settings.mouse.max_pitch;
+                camera.orbit.x += turn.x;
+                camera.orbit.y = (camera.orbit.y + turn.y).clamp(-max_pitch, max_pitch);
+            }
+            | CameraMode::FreeFly => {
+                let Some(direction) = look.look_direction() else {
+                    continue;
+                };
+                let right = direction.cross(Vec3::Y).normalize_or_zero();
+                let pitched = Quat::from_axis_angle(right, turn.y) * direction;
+                // Do not tip over the top or the bottom
+                let direction = if pitched.y.abs() < settings.mouse.max_pitch.sin() {
+                    pitched
+                } else {
+                    direction
+                };
+                let distance = look.target.distance(look.eye);
+                look.target = look.eye + Quat::from_rotation_y(turn.x) * direction * distance;
+            }
+        }
+    }
+}
+
+/// Tilts a following camera with the gamepads of the players.
+fn stick_look(
+    time: Res<Time>,
+    input: ActionInput,
+    settings: Res<CameraSettings>,
+    gamepads: Query<&PlayerGamepad>,
+    mut cameras: Query<&mut GameCamera>,
+) {
+    let tilt: f32 = gamepads
+        .iter()
+        .map(|&PlayerGamepad(gamepad)| {
+            input.gamepad_value(gamepad, Action::LookUp)
+                - input.gamepad_value(gamepad, Action::LookDown)
+        })
+        .sum();
+    if tilt == 0.0 {
+        return;
+    }
+    let max_pitch = settings.mouse.max_pitch;
+    for mut camera in &mut cameras {
+        if matches!(
+            camera.mode,
+            CameraMode::ThirdPerson | CameraMode::FirstPerson
+        ) {
+            let pitch = camera.orbit.y + tilt * settings.mouse.stick_speed * time.delta_seconds();
+            camera.orbit.y = pitch.clamp(-max_pitch, max_pitch);
+        }
+    }
+}
+
+/// Finds the floor point under a free cursor by casting a ray from the camera
+/// onto the plane `y = 0`.
+fn aim_cursor(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform, &GameCamera)>,
+    mut aim: ResMut<CursorAim>,
+) {
+    let point = windows.get_single().ok().and_then(|window| {
+        let cursor = window.cursor_position()?;
+        let (camera, transform, _) = cameras
+            .iter()
+            .find(|(_, _, camera)| !camera.mode.mouse_look())?;
+        let ray = camera.viewport_to_world(transform, cursor)?;
+        let distance = ray.intersect_plane(Vec3::ZERO, Vec3::Y)?;
+        Some(ray.get_point(distance))
+    });
+    if aim.point != point {
+        aim.point = point;
+    }
+}
+
+/// Moves a free-flying camera with the fly actions, by default
+/// the arrow keys, Page Up and Page Down.
+fn fly(
+    time: Res<Time>,
+    input: ActionInput,
+    settings: Res<CameraSettings>,
+    mut cameras: Query<(&GameCamera, &mut LookTransform)>,
+) {
+    let axis = |positive, negative| input.keyboard_value(positive) - input.keyboard_value(negative);
+    let forward = axis(Action::FlyForward, Action::FlyBack);
+    let right = axis(Action::FlyRight, Action::FlyLeft);
+    let up = axis(Action::FlyUp, Action::FlyDown);
+
+    for (camera, mut look) in &mut cameras {
+        if camera.mode != CameraMode::FreeFly {
+            continue;
+        }
+        let Some(direction) = look.look_direction() else {
+            continue;
+        };
+        let flat_forward = (direction * Vec3::new(1.0, 0.0, 1.0)).normalize_or_zero();
+        let flat_right = flat_forward.cross(Vec3::Y);
+        let velocity = (flat_forward * forward + flat_right * right + Vec3::Y * up)
+            .normalize_or_zero()
+            * settings.free_fly.speed;
+
+        let step = velocity * time.delta_seconds();
+        look.eye += step;
+        look.target += step;
+    }
+}
+
+/// Where a camera shared by several targets should aim: at their middle, turned
+/// as the first player, and how far the farthest target is from that middle.
+pub fn framing<'a>(
+    targets: impl Iterator<Item = (&'a Transform, Option<&'a PlayerId>)>,
+) -> Option<(Transform, f32)> {
+    let targets: Vec<_> = targets.collect();
+    let (lead, _) = targets.iter().min_by_key(|(_, id)| *id)?;
+    let center = targets
+        .iter()
+        .map(|(transform, _)| transform.translation)
+        .sum::<Vec3>()
+        / targets.len() as f32;
+    let spread = targets
+        .iter()
+        .map(|(transform, _)| transform.translation.distance(center))
+        .fold(0.0, f32::max);
+    Some((lead.with_translation(center), spread))
+}
+
+/// Follows the targets. In local co-op all players share the camera, which
+/// backs off as they spread apart; in first person it is the first player's eyes.
+fn follow_target(
+    settings: Res<CameraSettings>,
+    targets: Query<(&Transform, Option<&PlayerId>), With<CameraTarget>>,
+    mut cameras: Query<(&GameCamera, &mut LookTransform)>,
+) {
+    let Some((shared, spread)) = framing(targets.iter()) else {
+        return;
+    };
+    let lead = targets
+        .iter()
+        .min_by_key(|(_, id)| *id)
+        .map(|(transform, _)| transform);
+    for (camera, mut look) in &mut cameras {
+        let target = match camera.mode {
+            | CameraMode::FirstPerson => lead.unwrap_or(&shared),
+            | _ => &shared,
+        };
+        let Some(mut new_look) = settings.look(camera.mode, camera.orbit, target) else {
+            continue;
+        };
+        if camera.mode != CameraMode::FirstPerson {
+            let back = (new_look.eye - new_look.target).normalize_or_zero();
+            new_look.eye += back * spread * settings.framing_margin;
+        }
+        *look = new_look;
+    }
+}
+
+/// Turns `direction`, given relative to the `camera` view (forward is -Z),
+/// into the same direction relative to the `character`. Camera pitch is ignored,
+/// so forward always means away from the camera along the floor.
+pub fn camera_relative(camera: &Transform, character: &Transform, direction: Vec3) -> Vec3 {
+    let forward = camera.forward() * Vec3::new(1.0, 0.0, 1.0);
+    if forward.length_squared() < f32::EPSILON {
+        return direction;
+    }
+    let yaw = Quat::from_rotation_y(f32::atan2(-forward.x, -forward.z));
+    character.rotation.inverse() * yaw * direction
+}
+
+/// Pulls a third-person camera in front of level geometry between it and the target.
+/// Only static bodies count: characters and projectiles do not push the camera.
+fn avoid_obstacles(
+    settings: Res<CameraSettings>,
+    spatial_query: SpatialQuery,
+    targets: Query<Entity, With<CameraTarget>>,
+    bodies: Query<&RigidBody>,
+    collider_parents: Query<&ColliderParent>,
+    mut cameras: Query<(&GameCamera, &mut LookTransform)>,
+) {
+    let settings = &settings.third_person;
+    let shape = Collider::ball(settings.collision_radius);
+    let filter = SpatialQueryFilter::new().without_entities(targets.iter());
+    let is_static = |entity| {
+        let body = collider_parents
+            .get(entity)
+            .map_or(entity, |parent| parent.get());
+        bodies.get(body) == Ok(&RigidBody::Static)
+    };
+
+    for (camera, mut look) in &mut cameras {
+        if camera.mode != CameraMode::ThirdPerson {
+            continue;
+        }
+        let offset = look.eye - look.target;
+        let distance = offset.length();
+        if distance <= settings.min_distance {
+            continue;
+        }
+        let direction = offset / distance;
+
+        let hit = spatial_query
+            .shape_hits(
+                &shape,
+                look.target,
+                Quat::IDENTITY,
+                direction,
+                distance,
+                8,
+                true,
+                filter.clone(),
+            )
+            .into_iter()
+            .filter(|hit| is_static(hit.entity))
+            .map(|hit| hit.time_of_impact)
+            .reduce(f32::min);
+        if let Some(time_of_impact) = hit {
+            look.eye = look.target + direction * time_of_impact.max(settings.min_distance);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controls::CONTROLS_PATH;
+    use crate::testing;
+    use smooth_bevy_cameras::LookTransformPlugin;
+
+    #[test]
+    fn test_modes() {
+        let settings = CameraSettings::default();
+        let target = Transform::from_xyz(4.0, 0.0, 8.0)
+            .with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2));
+
+        let top_down = settings
+            .look(CameraMode::TopDown, Vec2::ZERO, &target)
+            .unwrap();
+        assert_eq!(top_down.target, target.translation);
+        assert!(top_down.eye.y > 10.0);
+
+        // Turned to -X, so the third-person camera stays at +X
+        let third_person = settings
+            .look(CameraMode::ThirdPerson, Vec2::ZERO, &target)
+            .unwrap();
+        assert!(third_person.eye.x > target.translation.x + 5.0);
+        assert!((third_person.eye.z - 8.0).abs() < 1e-4);
+
+        let first_person = settings
+            .look(CameraMode::FirstPerson, Vec2::ZERO, &target)
+            .unwrap();
+        let direction = first_person.look_direction().unwrap();
+        assert!(direction.distance(Vec3::NEG_X) < 1e-4);
+
+        assert!(settings
+            .look(CameraMode::FreeFly, Vec2::ZERO, &target)
+            .is_none());
+
+        // Orbited half a turn, the camera is in front of the target
+        let orbit = Vec2::new(std::f32::consts::PI, 0.0);
+        let third_person = settings
+            .look(CameraMode::ThirdPerson, orbit, &target)
+            .unwrap();
+        assert!(third_person.eye.x < target.translation.x - 5.0);
+        let pitched = settings
+            .look(CameraMode::FirstPerson, Vec2::new(0.0, 0.5), &target)
+            .unwrap();
+        assert!(pitched.look_direction().unwrap().y > 0.4, "looks up");
+    }
+
+    #[test]
+    fn test_framing() {
+        let first = Transform::from_xyz(0.0, 0.0, 0.0)
+            .with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2));
+        let second = Transform::from_xyz(4.0, 0.0, 0.0);
+        let targets = [(&second, Some(&PlayerId(1))), (&first, Some(&PlayerId(0)))];
+
+        let (shared, spread) = framing(targets.into_iter()).unwrap();
+        assert_eq!(shared.translation, Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(
+            shared.rotation, first.rotation,
+            "turned as the first player"
+        );
+        assert_eq!(spread, 2.0);
+
+        let (alone, spread) = framing([(&second, None)].into_iter()).unwrap();
+        assert_eq!(alone.translation, second.translation);
+        assert_eq!(spread, 0.0);
+        assert!(framing(std::iter::empty()).is_none());
+    }
+
+    #[test]
+    fn test_camera_relative() {
+        // The camera looks along -X, the character faces +Z
+        let camera = Transform::from_xyz(10.0, 5.0, 0.0).looking_at(Vec3::ZERO, Vec3::Y);
+        let character = Transform::from_rotation(Quat::from_rotation_y(std::f32::consts::PI));
+
+        let forward = camera_relative(&camera, &character, Vec3::NEG_Z);
+        assert!((character.rotation * forward).distance(Vec3::NEG_X) < 1e-4);
+        let right = camera_relative(&camera, &character, Vec3::X);
+        assert!((character.rotation * right).distance(Vec3::NEG_Z) < 1e-4);
+
+        // Looking straight down, there is no forward to go by
+        let overhead = Transform::from_xyz(0.0, 10.0, 0.0).looking_at(Vec3::ZERO, Vec3::Z);
+        assert_eq!(
+            camera_relative(&overhead, &character, Vec3::NEG_Z),
+            Vec3::NEG_Z
+        );
+    }
+
+    #[test]
+    fn test_cycle_and_follow() {
+        let mut app = testing::app();
+        app.add_plugins((LookTransformPlugin, CameraPlugin))
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<MouseButton>>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .insert_resource(ActionMap::load(CONTROLS_PATH).unwrap());
+        let target = app
+            .world
+            .spawn((
+                CameraTarget,
+                Transform::from_xyz(4.0, 0.0, 8.0),
+                Visibility::Inherited,
+            ))
+            .id();
+        app.update();
+
+        let look = |app: &mut App| {
+            *app.world
+                .query_filtered::<&LookTransform, With<GameCamera>>()
+                .single(&app.world)
+        };
+        assert_eq!(look(&mut app).target, Vec3::new(4.0, 0.0, 8.0));
+
+        let press = |app: &mut App, key| {
+            let mut input = app.world.resource_mut::<Input<KeyCode>>();
+            input.clear();
+            input.press(key);
+            app.update();
+            app.world.resource_mut::<Input<KeyCode>>().release(key);
+        };
+        press(&mut app, KeyCode::C);
+        assert_eq!(
+            *app.world.get::<Visibility>(target).unwrap(),
+            Visibility::Hidden,
+            "the target is in the way in first person"
+        );
+
+        press(&mut app, KeyCode::C);
+        let before = look(&mut app);
+        app.world.get_mut::<Transform>(target).unwrap().translation = Vec3::ZERO;
+        press(&mut app, KeyCode::PageUp);
+        let after = look(&mut app);
+        assert_eq!(
+            after.target.x, before.target.x,
+            "free fly followed the target"
+        );
+        assert!(after.eye.y > before.eye.y);
+        assert_eq!(
+            *app.world.get::<Visibility>(target).unwrap(),
+            Visibility::Inherited
+        );
+    }
+}
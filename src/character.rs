@@ -1,6 +1,28 @@
 use bevy::{ecs::query::Has, prelude::*};
-use bevy_xpbd_3d::{math::*, prelude::*, SubstepSchedule, SubstepSet};
-use smooth_bevy_cameras::{LookAngles, LookTransform};
+use bevy_xpbd_3d::{math::*, prelude::*, PhysicsSet, SubstepSchedule, SubstepSet};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{camera_relative, CameraSettings, CursorAim, GameCamera};
+use crate::controls::{Action, ActionInput};
+
+mod abilities;
+
+pub use abilities::{Burst, Dash, DodgeRoll, Sprint, Stamina};
+
+/// How many times per second physics and character controllers are stepped.
+pub const FIXED_HZ: f64 = 64.0;
+
+/// Runs physics in [`FixedUpdate`], one step of [`FIXED_HZ`] per fixed update,
+/// so that characters move the same whatever the frame rate.
+pub struct FixedPhysicsPlugin;
+
+impl Plugin for FixedPhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(PhysicsPlugins::new(FixedUpdate))
+            .insert_resource(Time::<Fixed>::from_hz(FIXED_HZ))
+            .insert_resource(Time::new_with(Physics::fixed_once_hz(FIXED_HZ)));
+    }
+}
 
 pub struct CharacterControllerPlugin;
 
@@ -9,17 +31,31 @@ impl Plugin for CharacterControllerPlugin {
         app.add_event::<MovementAction>()
             .add_systems(
                 Update,
+                (assign_gamepads, keyboard_input, gamepad_input)
+                    .chain()
+                    .in_set(PlayerInput),
+            )
+            // Actions of the whole frame, from players and AI alike
+            .add_systems(PostUpdate, gather_intents)
+            .add_systems(
+                FixedUpdate,
                 (
-                    keyboard_input,
-                    gamepad_input,
                     update_grounded,
                     apply_deferred,
                     apply_gravity,
+                    abilities::regenerate_stamina,
+                    abilities::sprint,
                     movement,
                     apply_movement_damping,
                     apply_rotation_damping,
+                    abilities::use_bursts,
+                    detect_ledges,
+                    step_up,
+                    snap_to_ground,
                 )
-                    .chain(),
+                    .chain()
+                    .in_set(ControllerStep)
+                    .before(PhysicsSet::Prepare),
             )
             .add_systems(
                 // Run collision handling in substep schedule
@@ -29,18 +65,86 @@ impl Plugin for CharacterControllerPlugin {
     }
 }
 
-/// An event sent for a movement input action.
+/// The systems that turn keyboard, mouse and gamepad input into [`MovementAction`]s
+/// for players. A replay turns them off and sends recorded actions instead.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlayerInput;
+
+/// The systems that step character controllers in [`FixedUpdate`],
+/// taking their [`MovementIntent`]s.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ControllerStep;
+
+/// An event sent for a movement action of a single character controller.
 #[derive(Event)]
-pub enum MovementAction {
+pub struct MovementAction {
+    pub entity: Entity,
+    pub kind: MovementKind,
+}
+
+/// The kind of a [`MovementAction`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum MovementKind {
     Move(Vector3),
-    Rotate(i8),
+    /// Turn left for positive values and right for negative ones, up to 1.
+    Rotate(Scalar),
     Jump,
+    Attack,
+    /// Shoot in a direction given, like [`MovementKind::Move`], relative to the character.
+    Shoot(Vector3),
+    /// Turn at once to face a direction given in world space, such as toward the cursor.
+    Aim(Vector3),
+    /// Use whatever is next to the character.
+    Interact,
+    /// Sent every frame while sprinting is wanted.
+    Sprint,
+    /// Dash in a direction given, like [`MovementKind::Move`], relative to the character.
+    Dash(Vector3),
+    /// Roll in a direction given, like [`MovementKind::Move`], relative to the character.
+    Dodge(Vector3),
 }
 
 /// A marker component indicating that an entity is using a character controller.
 #[derive(Component)]
 pub struct CharacterController;
 
+/// The movement asked of a character controller by the [`MovementAction`]s
+/// of the last frame. Actions come once a frame while the controller steps
+/// at a fixed rate, so they are applied on every fixed step until the next frame.
+///
+/// The fixed steps of a frame run before [`Update`], where input is read,
+/// so input reaches the controller one frame late.
+#[derive(Component, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct MovementIntent {
+    pub direction: Vector3,
+    pub rotation: Scalar,
+    /// Kept until a fixed step takes it, so that a short frame does not lose a jump.
+    pub jump: bool,
+    pub aim: Option<Vector3>,
+    pub sprint: bool,
+    pub dash: Option<Vector3>,
+    pub dodge: Option<Vector3>,
+    /// Kept, like `jump`, until the fixed step of a weapon takes it.
+    pub attack: bool,
+    /// Kept, like `attack`, until a ranged weapon takes it.
+    pub shoot: Option<Vector3>,
+}
+
+/// A marker component indicating that a character controller
+/// is driven by keyboard and gamepad input.
+#[derive(Component)]
+pub struct PlayerControlled;
+
+/// Which player a [`PlayerControlled`] character belongs to in local co-op.
+/// The keyboard and mouse drive the first player, number zero.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PlayerId(pub usize);
+
+/// The gamepad that drives a [`PlayerControlled`] character. Each connected
+/// gamepad is given to one character that has none, in order of [`PlayerId`].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerGamepad(pub Gamepad);
+
 /// A marker component indicating that an entity is on the ground.
 #[derive(Component)]
 #[component(storage = "SparseSet")]
@@ -75,8 +179,55 @@ pub struct ControllerGravity(Vector);
 #[derive(Component)]
 pub struct MaxSlopeAngle(Scalar);
 
+/// The highest step a grounded character controller climbs without jumping.
+/// Optional; without it steps and tile seams stop the character.
+#[derive(Component)]
+pub struct StepUp(pub Scalar);
+
+/// How far down a character controller that is not jumping is pulled
+/// to the ground, so that it walks down slopes and steps instead of
+/// flying off them. Optional.
+#[derive(Component)]
+pub struct GroundSnap(pub Scalar);
+
+/// Looks for a drop deeper than `max_drop` in front of a moving character
+/// controller and marks it [`AtLedge`]. Optional.
+#[derive(Component)]
+pub struct LedgeDetection {
+    /// How far ahead of the character the ground is checked.
+    pub distance: Scalar,
+    pub max_drop: Scalar,
+    /// Whether the character stops at the ledge instead of walking off.
+    pub stop: bool,
+}
+
+impl LedgeDetection {
+    pub fn new(max_drop: Scalar) -> Self {
+        Self {
+            distance: 0.5,
+            max_drop,
+            stop: false,
+        }
+    }
+
+    pub fn stopping(mut self) -> Self {
+        self.stop = true;
+        self
+    }
+}
+
+/// A marker component indicating that a character controller
+/// with [`LedgeDetection`] is moving toward a ledge.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct AtLedge;
+
 /// A bundle that contains the components needed for a basic
 /// kinematic character controller.
+///
+/// [`StepUp`], [`GroundSnap`] and [`LedgeDetection`] are optional
+/// and are inserted next to the bundle, as are the movement abilities
+/// [`Sprint`], [`Dash`] and [`DodgeRoll`] with the [`Stamina`] they use.
 #[derive(Bundle)]
 pub struct CharacterControllerBundle {
     character_controller: CharacterController,
@@ -84,6 +235,7 @@ pub struct CharacterControllerBundle {
     collider: Collider,
     ground_caster: ShapeCaster,
     gravity: ControllerGravity,
+    intent: MovementIntent,
     movement: MovementBundle,
 }
 
@@ -142,6 +294,7 @@ impl CharacterControllerBundle {
             )
             .with_max_time_of_impact(0.2),
             gravity: ControllerGravity(gravity),
+            intent: MovementIntent::default(),
             movement: MovementBundle::default(),
         }
     }
@@ -167,69 +320,191 @@ impl CharacterControllerBundle {
     }
 }
 
-/// Sends [`MovementAction`] events based on keyboard input.
+/// Turns a movement direction from the input into one relative to the character,
+/// following [`CameraSettings::camera_relative`].
+fn input_direction(
+    settings: &CameraSettings,
+    cameras: &Query<&Transform, With<GameCamera>>,
+    character: &Transform,
+    direction: Vector3,
+) -> Vector3 {
+    match cameras.iter().next() {
+        | Some(camera) if settings.camera_relative => camera_relative(camera, character, direction),
+        | _ => direction,
+    }
+}
+
+/// Sends [`MovementAction`] events based on keyboard and mouse input.
 fn keyboard_input(
     mut movement_event_writer: EventWriter<MovementAction>,
-    keyboard_input: Res<Input<KeyCode>>,
+    input: ActionInput,
+    settings: Res<CameraSettings>,
+    aim: Res<CursorAim>,
+    cameras: Query<&Transform, With<GameCamera>>,
+    players: Query<(Entity, &Transform, &PlayerId), With<PlayerControlled>>,
 ) {
-    let up = keyboard_input.any_pressed([KeyCode::W]);
-    let down = keyboard_input.any_pressed([KeyCode::S]);
-    let left = keyboard_input.any_pressed([KeyCode::A]);
-    let right = keyboard_input.any_pressed([KeyCode::D]);
+    let value = |action| input.keyboard_value(action);
+    let direction = Vector3::new(
+        value(Action::MoveRight) - value(Action::MoveLeft),
+        0.0,
+        value(Action::MoveBack) - value(Action::MoveForward),
+    )
+    .clamp_length_max(1.0);
+    let rotation = value(Action::RotateLeft) - value(Action::RotateRight);
 
-    let rotation_left = keyboard_input.any_pressed([KeyCode::Q]);
-    let rotation_right = keyboard_input.any_pressed([KeyCode::E]);
+    for (entity, transform, _) in players.iter().filter(|(_, _, id)| id.0 == 0) {
+        let mut send = |kind| movement_event_writer.send(MovementAction { entity, kind });
 
-    let rotation = rotation_left as i8 - rotation_right as i8;
+        if direction != Vector3::ZERO {
+            send(MovementKind::Move(input_direction(
+                &settings, &cameras, transform, direction,
+            )));
+        }
 
-    let horizontal = right as i8 - left as i8;
-    let vertical = up as i8 - down as i8;
-    let direction = Vector3::new(horizontal as Scalar, 0.0 as Scalar, -vertical as Scalar)
-        .clamp_length_max(1.0);
+        if let Some(point) = aim.point {
+            send(MovementKind::Aim(point - transform.translation));
+        }
+
+        if rotation != 0.0 {
+            send(MovementKind::Rotate(rotation));
+        }
+
+        if input.keyboard_just_pressed(Action::Jump) {
+            send(MovementKind::Jump);
+        }
+
+        if input.keyboard_just_pressed(Action::Attack) {
+            send(MovementKind::Attack);
+        }
+
+        if input.keyboard_just_pressed(Action::Shoot) {
+            send(MovementKind::Shoot(Vector3::NEG_Z));
+        }
+
+        if input.keyboard_just_pressed(Action::Interact) {
+            send(MovementKind::Interact);
+        }
+
+        if value(Action::Sprint) > 0.0 {
+            send(MovementKind::Sprint);
+        }
 
-    if direction != Vector3::ZERO {
-        movement_event_writer.send(MovementAction::Move(direction));
+        // Without a direction, dashes and rolls go forward
+        let burst = || {
+            input_direction(
+                &settings,
+                &cameras,
+                transform,
+                direction.try_normalize().unwrap_or(Vector3::NEG_Z),
+            )
+        };
+        if input.keyboard_just_pressed(Action::Dash) {
+            send(MovementKind::Dash(burst()));
+        }
+
+        if input.keyboard_just_pressed(Action::Dodge) {
+            send(MovementKind::Dodge(burst()));
+        }
     }
+}
 
-    if rotation != 0 {
-        movement_event_writer.send(MovementAction::Rotate(rotation));
+/// Gives each connected gamepad to a player without one
+/// and takes disconnected gamepads away.
+fn assign_gamepads(
+    mut commands: Commands,
+    gamepads: Res<Gamepads>,
+    players: Query<(Entity, &PlayerId, Option<&PlayerGamepad>), With<PlayerControlled>>,
+) {
+    let mut free_players: Vec<(Entity, &PlayerId)> = players
+        .iter()
+        .filter(|(_, _, owned)| owned.is_none())
+        .map(|(entity, id, _)| (entity, id))
+        .collect();
+    free_players.sort_by_key(|&(_, id)| id);
+    let mut free_players = free_players.into_iter().map(|(entity, _)| entity);
+    for gamepad in gamepads.iter() {
+        if players
+            .iter()
+            .any(|(_, _, owned)| owned == Some(&PlayerGamepad(gamepad)))
+        {
+            continue;
+        }
+        let Some(entity) = free_players.next() else {
+            break;
+        };
+        commands.entity(entity).insert(PlayerGamepad(gamepad));
     }
 
-    if keyboard_input.just_pressed(KeyCode::Space) {
-        movement_event_writer.send(MovementAction::Jump);
+    for (entity, _, owned) in &players {
+        if owned.is_some_and(|owned| !gamepads.contains(owned.0)) {
+            commands.entity(entity).remove::<PlayerGamepad>();
+        }
     }
 }
 
-/// Sends [`MovementAction`] events based on gamepad input.
+/// Sends [`MovementAction`] events based on the input of each player's own gamepad.
 fn gamepad_input(
     mut movement_event_writer: EventWriter<MovementAction>,
-    gamepads: Res<Gamepads>,
-    axes: Res<Axis<GamepadAxis>>,
-    buttons: Res<Input<GamepadButton>>,
+    input: ActionInput,
+    settings: Res<CameraSettings>,
+    cameras: Query<&Transform, With<GameCamera>>,
+    players: Query<(Entity, &Transform, &PlayerGamepad), With<PlayerControlled>>,
 ) {
-    for gamepad in gamepads.iter() {
-        let axis_lx = GamepadAxis {
-            gamepad,
-            axis_type: GamepadAxisType::LeftStickX,
-        };
-        let axis_ly = GamepadAxis {
-            gamepad,
-            axis_type: GamepadAxisType::LeftStickY,
-        };
+    for (entity, transform, &PlayerGamepad(gamepad)) in &players {
+        let value = |action| input.gamepad_value(gamepad, action);
+        let direction = Vector3::new(
+            value(Action::MoveRight) - value(Action::MoveLeft),
+            0.0,
+            value(Action::MoveBack) - value(Action::MoveForward),
+        )
+        .clamp_length_max(1.0);
+        let rotation = value(Action::RotateLeft) - value(Action::RotateRight);
+        let mut send = |kind| movement_event_writer.send(MovementAction { entity, kind });
+
+        if direction != Vector3::ZERO {
+            send(MovementKind::Move(input_direction(
+                &settings, &cameras, transform, direction,
+            )));
+        }
+
+        if rotation != 0.0 {
+            send(MovementKind::Rotate(rotation));
+        }
+
+        if input.gamepad_just_pressed(gamepad, Action::Jump) {
+            send(MovementKind::Jump);
+        }
+
+        if input.gamepad_just_pressed(gamepad, Action::Attack) {
+            send(MovementKind::Attack);
+        }
+
+        if input.gamepad_just_pressed(gamepad, Action::Shoot) {
+            send(MovementKind::Shoot(Vector3::NEG_Z));
+        }
 
-        if let (Some(x), Some(y)) = (axes.get(axis_lx), axes.get(axis_ly)) {
-            movement_event_writer.send(MovementAction::Move(
-                Vector3::new(x as Scalar, 0.0 as Scalar, -y as Scalar).clamp_length_max(1.0),
-            ));
+        if input.gamepad_just_pressed(gamepad, Action::Interact) {
+            send(MovementKind::Interact);
         }
 
-        let jump_button = GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::South,
+        if value(Action::Sprint) > 0.0 {
+            send(MovementKind::Sprint);
+        }
+
+        let burst = || {
+            input_direction(
+                &settings,
+                &cameras,
+                transform,
+                direction.try_normalize().unwrap_or(Vector3::NEG_Z),
+            )
         };
+        if input.gamepad_just_pressed(gamepad, Action::Dash) {
+            send(MovementKind::Dash(burst()));
+        }
 
-        if buttons.just_pressed(jump_button) {
-            movement_event_writer.send(MovementAction::Jump);
+        if input.gamepad_just_pressed(gamepad, Action::Dodge) {
+            send(MovementKind::Dodge(burst()));
         }
     }
 }
@@ -261,54 +536,93 @@ fn update_grounded(
     }
 }
 
+/// Replaces each controller's [`MovementIntent`] with the actions of this frame,
+/// for the fixed steps of the next frame to apply.
+fn gather_intents(
+    mut movement_event_reader: EventReader<MovementAction>,
+    mut intents: Query<&mut MovementIntent>,
+) {
+    for mut intent in &mut intents {
+        intent.direction = Vector3::ZERO;
+        intent.rotation = 0.0;
+        intent.sprint = false;
+    }
+
+    for event in movement_event_reader.read() {
+        let Ok(mut intent) = intents.get_mut(event.entity) else {
+            continue;
+        };
+        match event.kind {
+            | MovementKind::Move(direction) => intent.direction += direction,
+            | MovementKind::Rotate(direction) => intent.rotation += direction.clamp(-1.0, 1.0),
+            | MovementKind::Jump => intent.jump = true,
+            | MovementKind::Aim(direction) => intent.aim = Some(direction),
+            | MovementKind::Sprint => intent.sprint = true,
+            | MovementKind::Dash(direction) => intent.dash = Some(direction),
+            | MovementKind::Dodge(direction) => intent.dodge = Some(direction),
+            | MovementKind::Attack => intent.attack = true,
+            | MovementKind::Shoot(direction) => intent.shoot = Some(direction),
+            // Interaction is handled by the dungeon
+            | MovementKind::Interact => {}
+        }
+    }
+}
+
+/// Applies the [`MovementIntent`] of each controller for one fixed step.
 fn movement(
     time: Res<Time>,
-    mut movement_event_reader: EventReader<MovementAction>,
     mut controllers: Query<(
         &MovementAcceleration,
         &MovementDampingFactor,
         &AngularAcceleration,
-        &AngularDampingFactor,
         &JumpImpulse,
         &Transform,
+        &mut MovementIntent,
+        &mut Rotation,
         &mut LinearVelocity,
         &mut AngularVelocity,
+        Option<&Sprint>,
         Has<Grounded>,
     )>,
 ) {
     let delta_time = time.delta_seconds_f64().adjust_precision();
 
-    for event in movement_event_reader.read() {
-        for (
-            movement_acceleration,
-            movement_damping_factor,
-            angular_acceleration,
-            angular_damping_factor,
-            jump_impulse,
-            transform,
-            mut linear_velocity,
-            mut angular_velocity,
-            is_grounded,
-        ) in &mut controllers
-        {
-            let rotation_matrix = Mat3::from_quat(transform.rotation);
-            match event {
-                | MovementAction::Move(direction) => {
-                    let new_dir = rotation_matrix.mul_vec3(*direction)
-                        * movement_acceleration.0
-                        * movement_damping_factor.0
-                        * delta_time;
-                    linear_velocity.x += new_dir.x;
-                    linear_velocity.z += new_dir.z;
-                }
-                | MovementAction::Rotate(direction) => {
-                    angular_velocity.y += (*direction as f32) * angular_acceleration.0 * delta_time;
-                }
-                | MovementAction::Jump => {
-                    if is_grounded {
-                        linear_velocity.y = jump_impulse.0;
-                    }
-                }
+    for (
+        movement_acceleration,
+        movement_damping_factor,
+        angular_acceleration,
+        jump_impulse,
+        transform,
+        mut intent,
+        mut rotation,
+        mut linear_velocity,
+        mut angular_velocity,
+        sprint,
+        is_grounded,
+    ) in &mut controllers
+    {
+        let sprint = sprint
+            .filter(|sprint| sprint.is_active())
+            .map_or(1.0, |sprint| sprint.multiplier);
+        let rotation_matrix = Mat3::from_quat(transform.rotation);
+        let new_dir = rotation_matrix.mul_vec3(intent.direction)
+            * movement_acceleration.0
+            * sprint
+            * movement_damping_factor.0
+            * delta_time;
+        linear_velocity.x += new_dir.x;
+        linear_velocity.z += new_dir.z;
+
+        angular_velocity.y += intent.rotation * angular_acceleration.0 * delta_time;
+
+        if std::mem::take(&mut intent.jump) && is_grounded {
+            linear_velocity.y = jump_impulse.0;
+        }
+
+        if let Some(direction) = intent.aim.take() {
+            if direction.x != 0.0 || direction.z != 0.0 {
+                rotation.0 = Quaternion::from_rotation_y(Scalar::atan2(-direction.x, -direction.z));
+                angular_velocity.y = 0.0;
             }
         }
     }
@@ -338,6 +652,210 @@ fn apply_rotation_damping(mut query: Query<(&AngularDampingFactor, &mut AngularV
     }
 }
 
+/// The gap kept between a character controller and the ground it is moved onto.
+const SKIN: Scalar = 0.01;
+
+/// How far above the ground a character controller looks for steps.
+const PROBE_LIFT: Scalar = 0.05;
+
+/// The horizontal direction and the distance a character moves during this step.
+fn horizontal_motion(velocity: &LinearVelocity, delta_time: Scalar) -> Option<(Vector, Scalar)> {
+    let horizontal = Vector::new(velocity.x, 0.0, velocity.z);
+    let distance = horizontal.length() * delta_time;
+    (distance > Scalar::EPSILON).then(|| (horizontal / horizontal.length(), distance))
+}
+
+/// Whether the surface of a shape hit is flat enough to stand on.
+// `Option::is_none_or` would need Rust 1.82
+#[allow(clippy::unnecessary_map_or)]
+fn is_walkable(
+    hit: &ShapeHitData,
+    rotation: &Rotation,
+    max_slope_angle: Option<&MaxSlopeAngle>,
+) -> bool {
+    max_slope_angle.map_or(true, |angle| {
+        rotation.rotate(-hit.normal2).angle_between(Vector::Y).abs() <= angle.0
+    })
+}
+
+/// Marks controllers that are moving toward a drop and stops those
+/// that should not walk off it.
+#[allow(clippy::type_complexity)]
+fn detect_ledges(
+    mut commands: Commands,
+    time: Res<Time>,
+    spatial_query: Res<SpatialQueryPipeline>,
+    mut controllers: Query<(
+        Entity,
+        &LedgeDetection,
+        &Position,
+        &mut LinearVelocity,
+        Has<Grounded>,
+        Has<AtLedge>,
+    )>,
+) {
+    let delta_time = time.delta_seconds_f64().adjust_precision();
+
+    for (entity, ledge, position, mut velocity, is_grounded, at_ledge) in &mut controllers {
+        let filter = SpatialQueryFilter::new().without_entities([entity]);
+        let ground = |origin: Vector, max_time_of_impact: Scalar| {
+            spatial_query
+                .cast_ray(
+                    origin,
+                    Vector::NEG_Y,
+                    max_time_of_impact,
+                    true,
+                    filter.clone(),
+                )
+                .map(|hit| hit.time_of_impact)
+        };
+        // The drop is measured from the ground right under the character
+        let edge = is_grounded
+            .then(|| horizontal_motion(&velocity, delta_time))
+            .flatten()
+            .and_then(|(direction, _)| {
+                let below = ground(position.0, Scalar::MAX)?;
+                let ahead = ground(
+                    position.0 + direction * ledge.distance,
+                    below + ledge.max_drop,
+                );
+                ahead.is_none().then_some(direction)
+            });
+
+        match edge {
+            | Some(direction) => {
+                if ledge.stop {
+                    let forward = velocity.dot(direction).max(0.0);
+                    velocity.0 -= direction * forward;
+                }
+                if !at_ledge {
+                    commands.entity(entity).insert(AtLedge);
+                }
+            }
+            | None if at_ledge => {
+                commands.entity(entity).remove::<AtLedge>();
+            }
+            | None => {}
+        }
+    }
+}
+
+/// Lifts grounded controllers onto steps no higher than their [`StepUp`],
+/// which the collision response alone would treat as walls.
+// `Option::is_none_or` would need Rust 1.82
+#[allow(clippy::type_complexity, clippy::unnecessary_map_or)]
+fn step_up(
+    time: Res<Time>,
+    spatial_query: Res<SpatialQueryPipeline>,
+    mut controllers: Query<
+        (
+            Entity,
+            &StepUp,
+            &Collider,
+            &Rotation,
+            &LinearVelocity,
+            &mut Position,
+            Option<&MaxSlopeAngle>,
+        ),
+        With<Grounded>,
+    >,
+) {
+    let delta_time = time.delta_seconds_f64().adjust_precision();
+
+    for (entity, step, collider, rotation, velocity, mut position, max_slope_angle) in
+        &mut controllers
+    {
+        let Some((direction, distance)) = horizontal_motion(velocity, delta_time) else {
+            continue;
+        };
+        let distance = distance + SKIN;
+        let filter = SpatialQueryFilter::new().without_entities([entity]);
+        let cast = |origin: Vector, direction: Vector, max_time_of_impact: Scalar| {
+            spatial_query.cast_shape(
+                collider,
+                origin,
+                rotation.0,
+                direction,
+                max_time_of_impact,
+                true,
+                filter.clone(),
+            )
+        };
+
+        // Only something too steep to walk up is a step. The probe is lifted
+        // a little so that it does not catch the ground the character stands on.
+        let Some(obstacle) = cast(position.0 + Vector::Y * PROBE_LIFT, direction, distance) else {
+            continue;
+        };
+        if is_walkable(&obstacle, rotation, max_slope_angle) {
+            continue;
+        }
+        // There must be room above the character and above the step
+        let raised = position.0 + Vector::Y * step.0;
+        if cast(position.0, Vector::Y, step.0).is_some()
+            || cast(raised, direction, distance).is_some()
+        {
+            continue;
+        }
+        let Some(top) = cast(raised + direction * distance, Vector::NEG_Y, step.0) else {
+            continue;
+        };
+        // The cast lands on the edge of the step, so its surface is checked with a ray
+        let surface = spatial_query.cast_ray(
+            Vector::new(obstacle.point1.x, raised.y, obstacle.point1.z) + direction * PROBE_LIFT,
+            Vector::NEG_Y,
+            Scalar::MAX,
+            true,
+            filter,
+        );
+        if surface.is_some_and(|surface| {
+            max_slope_angle.map_or(true, |angle| {
+                surface.normal.angle_between(Vector::Y) <= angle.0
+            })
+        }) {
+            position.y += step.0 - top.time_of_impact + SKIN;
+        }
+    }
+}
+
+/// Pulls controllers that are walking, not jumping, down to the ground below.
+#[allow(clippy::type_complexity)]
+fn snap_to_ground(
+    spatial_query: Res<SpatialQueryPipeline>,
+    mut controllers: Query<(
+        Entity,
+        &GroundSnap,
+        &Collider,
+        &Rotation,
+        &mut Position,
+        &mut LinearVelocity,
+        Option<&MaxSlopeAngle>,
+    )>,
+) {
+    for (entity, snap, collider, rotation, mut position, mut velocity, max_slope_angle) in
+        &mut controllers
+    {
+        if velocity.y > 0.0 {
+            continue;
+        }
+        let Some(ground) = spatial_query.cast_shape(
+            collider,
+            position.0,
+            rotation.0,
+            Vector::NEG_Y,
+            snap.0,
+            true,
+            SpatialQueryFilter::new().without_entities([entity]),
+        ) else {
+            continue;
+        };
+        if ground.time_of_impact > SKIN && is_walkable(&ground, rotation, max_slope_angle) {
+            position.y -= ground.time_of_impact - SKIN;
+            velocity.y = 0.0;
+        }
+    }
+}
+
 /// Kinematic bodies do not get pushed by collisions by default,
 /// so it needs to be done manually.
 ///
@@ -416,3 +934,242 @@ fn kinematic_controller_collisions(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::Invulnerability;
+    use crate::testing::{self, Trajectory};
+    use bevy::input::keyboard::KeyboardInput;
+    use bevy::input::ButtonState;
+    use std::time::Duration;
+
+    /// One second of fixed steps.
+    const STEPS: usize = FIXED_HZ as usize;
+
+    fn app(fps: u64) -> App {
+        testing::controller_app(Duration::from_nanos(1_000_000_000 / fps))
+    }
+
+    /// A static box with its top at `top`.
+    fn block(app: &mut App, size: Vec3, center: Vec2, top: f32) {
+        app.world.spawn((
+            RigidBody::Static,
+            Collider::cuboid(size.x, size.y, size.z),
+            TransformBundle::from_transform(Transform::from_xyz(
+                center.x,
+                top - size.y / 2.0,
+                center.y,
+            )),
+        ));
+    }
+
+    /// The player standing on the ground at `y`.
+    fn spawn_player(app: &mut App, y: f32) -> Entity {
+        testing::spawn_player(app, Vec3::new(0.0, y + REST, 0.0))
+    }
+
+    /// How high the player's center rests above the ground.
+    const REST: f32 = 0.625;
+
+    /// Presses the keys and holds them until the given number of fixed steps.
+    /// The keys are read in the first frame and only move the player
+    /// from the fixed steps of the second one on.
+    fn walk(app: &mut App, keys: &[KeyCode], steps: usize) -> Vec<Transform> {
+        // Sent as events, so that the keys count as just pressed in the next frame
+        for &key in keys {
+            app.world.send_event(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key),
+                state: ButtonState::Pressed,
+                window: Entity::PLACEHOLDER,
+            });
+        }
+        while app.world.resource::<Trajectory>().0.len() < steps {
+            app.update();
+        }
+        app.world.resource::<Trajectory>().0[..steps].to_vec()
+    }
+
+    /// The trajectory of a player who walks forward and turns left,
+    /// played at the given frame rate.
+    fn trajectory(fps: u64) -> Vec<Transform> {
+        let mut app = app(fps);
+        block(&mut app, Vec3::new(50.0, 1.0, 50.0), Vec2::ZERO, 0.0);
+        spawn_player(&mut app, 0.0);
+        walk(&mut app, &[KeyCode::W, KeyCode::Q], STEPS)
+    }
+
+    #[test]
+    fn test_frame_rate_independence() {
+        let expected = trajectory(60);
+        let last = expected[STEPS - 1];
+        assert!(
+            last.translation.xz().length() > 1.0,
+            "the player did not move"
+        );
+        assert!(
+            last.rotation.angle_between(Quat::IDENTITY) > 0.1,
+            "the player did not turn"
+        );
+        for fps in [30, 144] {
+            let actual = trajectory(fps);
+            for (step, (actual, expected)) in actual.iter().zip(&expected).enumerate() {
+                assert!(
+                    actual.translation.abs_diff_eq(expected.translation, 1e-4)
+                        && actual.rotation.abs_diff_eq(expected.rotation, 1e-4),
+                    "differs at {} fps on step {}",
+                    fps,
+                    step
+                );
+            }
+        }
+    }
+
+    /// A floor with a step of the given height starting two units ahead.
+    fn stairs(step: Option<f32>) -> Vec<Transform> {
+        let mut app = app(60);
+        block(&mut app, Vec3::new(50.0, 1.0, 50.0), Vec2::ZERO, 0.0);
+        block(
+            &mut app,
+            Vec3::new(4.0, 0.45, 4.0),
+            Vec2::new(0.0, -4.0),
+            0.45,
+        );
+        let player = spawn_player(&mut app, 0.0);
+        if let Some(step) = step {
+            app.world.entity_mut(player).insert(StepUp(step));
+        }
+        walk(&mut app, &[KeyCode::W], STEPS)
+    }
+
+    #[test]
+    fn test_step_up() {
+        let climbed = stairs(Some(0.5))[STEPS - 1].translation;
+        assert!(climbed.z < -3.0, "stopped at {}", climbed);
+        assert!((climbed.y - 0.45 - REST).abs() < 0.05, "at {}", climbed);
+
+        let blocked = stairs(None)[STEPS - 1].translation;
+        assert!(blocked.z > -2.0, "climbed to {}", blocked);
+    }
+
+    #[test]
+    fn test_ground_snap() {
+        let mut app = app(60);
+        block(&mut app, Vec3::new(50.0, 1.0, 50.0), Vec2::ZERO, 0.0);
+        block(&mut app, Vec3::new(4.0, 0.3, 4.0), Vec2::ZERO, 0.3);
+        let player = spawn_player(&mut app, 0.3);
+        app.world.entity_mut(player).insert(GroundSnap(0.5));
+
+        let trajectory = walk(&mut app, &[KeyCode::W], STEPS);
+        let off = trajectory
+            .iter()
+            .filter(|transform| transform.translation.z < -2.6)
+            .collect::<Vec<_>>();
+        assert!(!off.is_empty(), "the player did not walk off the step");
+        for transform in off {
+            assert!(
+                (transform.translation.y - REST).abs() < 0.05,
+                "flew off to {}",
+                transform.translation
+            );
+        }
+    }
+
+    #[test]
+    fn test_stop_at_ledge() {
+        let mut app = app(60);
+        block(&mut app, Vec3::new(4.0, 1.0, 4.0), Vec2::ZERO, 0.0);
+        let player = spawn_player(&mut app, 0.0);
+        app.world
+            .entity_mut(player)
+            .insert(LedgeDetection::new(1.0).stopping());
+
+        let last = walk(&mut app, &[KeyCode::W], STEPS)[STEPS - 1].translation;
+        assert!(last.z < -1.0 && last.z > -2.0, "stopped at {}", last);
+        assert!((last.y - REST).abs() < 0.05, "fell to {}", last);
+        assert!(app.world.get::<AtLedge>(player).is_some());
+    }
+
+    /// How far the player walks forward in a second on a flat floor.
+    fn distance_walked(keys: &[KeyCode], stamina: f32) -> (f32, f32) {
+        let mut app = app(60);
+        block(&mut app, Vec3::new(50.0, 1.0, 50.0), Vec2::ZERO, 0.0);
+        let player = spawn_player(&mut app, 0.0);
+        let mut full = Stamina::new(100.0, 0.0);
+        full.current = stamina;
+        app.world
+            .entity_mut(player)
+            .insert((full, Sprint::new(1.5, 20.0)));
+        let last = walk(&mut app, keys, STEPS)[STEPS - 1].translation;
+        let stamina = app.world.get::<Stamina>(player).unwrap().current;
+        (-last.z, stamina)
+    }
+
+    #[test]
+    fn test_sprint() {
+        let (walked, rested) = distance_walked(&[KeyCode::W], 100.0);
+        let (sprinted, tired) = distance_walked(&[KeyCode::W, KeyCode::ShiftLeft], 100.0);
+        assert!(sprinted > walked * 1.3, "{} against {}", sprinted, walked);
+        assert_eq!(rested, 100.0);
+        assert!((tired - 80.0).abs() < 1.0, "{} stamina left", tired);
+
+        let (exhausted, _) = distance_walked(&[KeyCode::W, KeyCode::ShiftLeft], 0.0);
+        assert_eq!(exhausted, walked);
+    }
+
+    #[test]
+    fn test_dash_and_roll() {
+        let mut app = app(60);
+        block(&mut app, Vec3::new(50.0, 1.0, 50.0), Vec2::ZERO, 0.0);
+        let player = spawn_player(&mut app, 0.0);
+        app.world.entity_mut(player).insert((
+            Stamina::new(30.0, 10.0),
+            Dash(Burst::new(20.0, 0.15, 0.5).with_invulnerability(0.15)),
+            DodgeRoll(
+                Burst::new(10.0, 0.4, 0.5)
+                    .with_invulnerability(0.3)
+                    .with_stamina(20.0),
+            ),
+        ));
+        let position = |app: &App| app.world.get::<Transform>(player).unwrap().translation;
+        let invulnerable = |app: &App| {
+            app.world
+                .get::<Invulnerability>(player)
+                .is_some_and(|invulnerability| invulnerability.is_active())
+        };
+        // Let the player land before dashing
+        walk(&mut app, &[], 4);
+
+        // A dash without a direction goes forward and makes the player invulnerable
+        walk(&mut app, &[KeyCode::V], 8);
+        assert!(invulnerable(&app));
+        walk(&mut app, &[], 16);
+        assert!(position(&app).z < -2.0, "dashed to {}", position(&app));
+        // The dash cannot be repeated while it cools down
+        let dashing = |app: &App| app.world.get::<Dash>(player).unwrap().0.is_active();
+        let rolling = |app: &App| app.world.get::<DodgeRoll>(player).unwrap().0.is_active();
+        app.world.resource_mut::<Input<KeyCode>>().release_all();
+        walk(&mut app, &[KeyCode::V], 20);
+        assert!(!dashing(&app));
+
+        // The roll goes to the right and costs stamina
+        app.world.resource_mut::<Input<KeyCode>>().release_all();
+        walk(&mut app, &[], 60);
+        let start = position(&app);
+        walk(&mut app, &[KeyCode::X, KeyCode::D], 64);
+        assert!(rolling(&app));
+        walk(&mut app, &[], 100);
+        assert!(
+            position(&app).x - start.x > 3.0,
+            "rolled to {}",
+            position(&app)
+        );
+        assert_eq!(app.world.get::<Stamina>(player).unwrap().current, 10.0);
+
+        // Another roll needs more stamina than is left
+        app.world.resource_mut::<Input<KeyCode>>().release_all();
+        walk(&mut app, &[KeyCode::X], 104);
+        assert!(!rolling(&app));
+    }
+}
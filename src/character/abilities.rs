@@ -0,0 +1,273 @@
+//! Movement abilities of character controllers: sprinting, which uses up
+//! [`Stamina`], and short bursts of speed, [`Dash`] and [`DodgeRoll`],
+//! which can make the character invulnerable while they last.
+
+use bevy::{ecs::query::Has, prelude::*};
+use bevy_xpbd_3d::{math::*, prelude::*};
+
+use super::{Grounded, MovementAcceleration, MovementIntent};
+use crate::health::Invulnerability;
+
+/// What sprinting and dodging use up. It comes back by itself
+/// once the character has not used any for `regeneration_delay` seconds.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+    /// Stamina regained per second.
+    pub regeneration: f32,
+    pub regeneration_delay: f32,
+    since_used: f32,
+}
+
+impl Stamina {
+    pub fn new(max: f32, regeneration: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            regeneration,
+            regeneration_delay: 1.0,
+            since_used: 0.0,
+        }
+    }
+
+    /// Spends `amount` if there is that much left.
+    pub fn spend(&mut self, amount: f32) -> bool {
+        if self.current < amount {
+            return false;
+        }
+        self.current -= amount;
+        self.since_used = 0.0;
+        true
+    }
+
+    /// Spends as much of `amount` as is left.
+    fn drain(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.0);
+        self.since_used = 0.0;
+    }
+}
+
+/// Faster movement while the sprint action is held and there is [`Stamina`] left.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Sprint {
+    /// How many times faster the character accelerates.
+    pub multiplier: Scalar,
+    /// Stamina used per second of sprinting.
+    pub stamina: f32,
+    active: bool,
+}
+
+impl Sprint {
+    pub fn new(multiplier: Scalar, stamina: f32) -> Self {
+        Self {
+            multiplier,
+            stamina,
+            active: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+/// A burst of speed in one direction that steering cannot change.
+#[derive(Debug, Clone, Copy)]
+pub struct Burst {
+    pub speed: Scalar,
+    /// How many seconds the burst lasts.
+    pub duration: f32,
+    /// How many seconds must pass after a burst starts before the next one.
+    pub cooldown: f32,
+    /// How many seconds from the start the character ignores damage.
+    pub invulnerability: f32,
+    /// Stamina used by each burst.
+    pub stamina: f32,
+    direction: Vector,
+    remaining: f32,
+    cooldown_remaining: f32,
+}
+
+impl Burst {
+    pub fn new(speed: Scalar, duration: f32, cooldown: f32) -> Self {
+        Self {
+            speed,
+            duration,
+            cooldown,
+            invulnerability: 0.0,
+            stamina: 0.0,
+            direction: Vector::ZERO,
+            remaining: 0.0,
+            cooldown_remaining: 0.0,
+        }
+    }
+
+    pub fn with_invulnerability(mut self, seconds: f32) -> Self {
+        self.invulnerability = seconds;
+        self
+    }
+
+    pub fn with_stamina(mut self, stamina: f32) -> Self {
+        self.stamina = stamina;
+        self
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.remaining > 0.0
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.cooldown_remaining <= 0.0
+    }
+
+    /// Starts the burst in a world space direction if it is ready
+    /// and the character has the stamina for it.
+    fn start(&mut self, direction: Vector, stamina: Option<&mut Stamina>) -> bool {
+        let direction = Vector::new(direction.x, 0.0, direction.z).normalize_or_zero();
+        if !self.is_ready() || direction == Vector::ZERO {
+            return false;
+        }
+        if self.stamina > 0.0 && stamina.is_some_and(|stamina| !stamina.spend(self.stamina)) {
+            return false;
+        }
+        self.direction = direction;
+        self.remaining = self.duration;
+        self.cooldown_remaining = self.cooldown;
+        true
+    }
+
+    fn tick(&mut self, delta_time: f32) {
+        self.remaining = (self.remaining - delta_time).max(0.0);
+        self.cooldown_remaining = (self.cooldown_remaining - delta_time).max(0.0);
+    }
+}
+
+/// A quick dash in any direction, even in the air. The character
+/// does not fall while dashing.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Dash(pub Burst);
+
+/// A roll along the ground. It can only be started on the ground.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct DodgeRoll(pub Burst);
+
+pub(super) fn regenerate_stamina(time: Res<Time>, mut query: Query<&mut Stamina>) {
+    let delta_time = time.delta_seconds();
+    for mut stamina in &mut query {
+        stamina.since_used += delta_time;
+        if stamina.since_used >= stamina.regeneration_delay {
+            stamina.current =
+                (stamina.current + stamina.regeneration * delta_time).min(stamina.max);
+        }
+    }
+}
+
+/// Turns sprinting on for characters that move with the sprint action held
+/// and have stamina left, and spends the stamina.
+pub(super) fn sprint(
+    time: Res<Time>,
+    mut query: Query<(&MovementIntent, &mut Sprint, Option<&mut Stamina>)>,
+) {
+    let delta_time = time.delta_seconds();
+    for (intent, mut sprint, stamina) in &mut query {
+        let wants = intent.sprint && intent.direction != Vector::ZERO;
+        sprint.active = match stamina {
+            | Some(mut stamina) if wants && stamina.current > 0.0 => {
+                stamina.drain(sprint.stamina * delta_time);
+                true
+            }
+            | Some(_) => false,
+            | None => wants,
+        };
+    }
+}
+
+/// Starts dashes and rolls the characters asked for and moves
+/// the characters whose bursts are under way.
+#[allow(clippy::type_complexity)]
+pub(super) fn use_bursts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut MovementIntent,
+            &mut LinearVelocity,
+            Option<&mut Dash>,
+            Option<&mut DodgeRoll>,
+            Option<&mut Stamina>,
+            Option<&mut Invulnerability>,
+            Has<Grounded>,
+        ),
+        With<MovementAcceleration>,
+    >,
+) {
+    let delta_time = time.delta_seconds();
+
+    for (
+        entity,
+        transform,
+        mut intent,
+        mut velocity,
+        dash,
+        roll,
+        mut stamina,
+        mut invulnerability,
+        is_grounded,
+    ) in &mut query
+    {
+        // Directions are asked for relative to the character, like movement
+        let mut started = None;
+        let dash_direction = intent.dash.take();
+        let roll_direction = intent.dodge.take().filter(|_| is_grounded);
+
+        let mut dash = dash.map(|dash| dash.into_inner());
+        let mut roll = roll.map(|roll| roll.into_inner());
+        let busy = dash.as_ref().is_some_and(|dash| dash.0.is_active())
+            || roll.as_ref().is_some_and(|roll| roll.0.is_active());
+        if !busy {
+            if let (Some(Dash(burst)), Some(direction)) = (dash.as_mut(), dash_direction) {
+                if burst.start(transform.rotation * direction, stamina.as_deref_mut()) {
+                    started = Some(burst.invulnerability);
+                }
+            }
+            if let (Some(DodgeRoll(burst)), Some(direction), None) =
+                (roll.as_mut(), roll_direction, started)
+            {
+                if burst.start(transform.rotation * direction, stamina.as_deref_mut()) {
+                    started = Some(burst.invulnerability);
+                }
+            }
+        }
+
+        if let Some(seconds) = started.filter(|&seconds| seconds > 0.0) {
+            match invulnerability.as_deref_mut() {
+                | Some(invulnerability) => {
+                    invulnerability.remaining = invulnerability.remaining.max(seconds);
+                }
+                | None => {
+                    commands.entity(entity).insert(Invulnerability {
+                        duration: 0.0,
+                        remaining: seconds,
+                    });
+                }
+            }
+        }
+
+        if let Some(Dash(burst)) = dash {
+            if burst.is_active() {
+                velocity.0 = burst.direction * burst.speed;
+            }
+            burst.tick(delta_time);
+        }
+        if let Some(DodgeRoll(burst)) = roll {
+            if burst.is_active() {
+                velocity.x = burst.direction.x * burst.speed;
+                velocity.z = burst.direction.z * burst.speed;
+            }
+            burst.tick(delta_time);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Combat between characters: weapons turn attack actions into [`Damage`].
+//!
+//! [`Damage`]: crate::health::Damage
+
+mod melee;
+mod projectile;
+
+pub use melee::MeleeAttack;
+pub use projectile::RangedAttack;
+
+use bevy::prelude::*;
+use bevy_xpbd_3d::PhysicsSet;
+
+use crate::character::ControllerStep;
+use crate::health::apply_damage;
+
+/// Weapons take the attacks of [`MovementIntent`]s on the same fixed steps
+/// as the controllers, so that hits and knockback do not depend on the frame rate.
+///
+/// [`MovementIntent`]: crate::character::MovementIntent
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<projectile::ProjectilePool>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    melee::tick_cooldowns,
+                    melee::expire_hitboxes,
+                    melee::start_attacks,
+                    melee::resolve_hits,
+                )
+                    .chain()
+                    .after(ControllerStep)
+                    .before(apply_damage)
+                    .before(PhysicsSet::Prepare),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    projectile::tick_cooldowns,
+                    projectile::expire_projectiles,
+                    projectile::launch,
+                    projectile::resolve_hits,
+                )
+                    .chain()
+                    .after(ControllerStep)
+                    .before(apply_damage)
+                    .before(PhysicsSet::Prepare),
+            );
+    }
+}
@@ -0,0 +1,229 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+
+use crate::character::MovementIntent;
+use crate::health::{Damage, Health};
+
+/// A melee weapon: on [`MovementKind::Attack`] it opens a short-lived sensor
+/// hitbox in front of its owner.
+///
+/// [`MovementKind::Attack`]: crate::character::MovementKind::Attack
+#[derive(Component)]
+pub struct MeleeAttack {
+    pub damage: f32,
+    /// Distance from the owner to the center of the hitbox along its forward axis.
+    pub reach: f32,
+    /// Full size of the hitbox.
+    pub size: Vec3,
+    /// How many seconds the hitbox stays active.
+    pub active_time: f32,
+    /// How many seconds must pass between two attacks.
+    pub cooldown: f32,
+    /// Horizontal speed given to anything the hitbox hits.
+    pub knockback: f32,
+    ready_in: f32,
+}
+
+impl MeleeAttack {
+    pub fn new(damage: f32, reach: f32) -> Self {
+        Self {
+            damage,
+            reach,
+            size: Vec3::new(1.2, 1.0, 1.0),
+            active_time: 0.2,
+            cooldown: 0.5,
+            knockback: 8.0,
+            ready_in: 0.0,
+        }
+    }
+}
+
+/// An active hitbox. Every entity it touches is hit at most once.
+#[derive(Component)]
+pub struct Hitbox {
+    owner: Entity,
+    damage: f32,
+    knockback: f32,
+    remaining: f32,
+    hit: Vec<Entity>,
+}
+
+pub(super) fn tick_cooldowns(time: Res<Time>, mut weapons: Query<&mut MeleeAttack>) {
+    for mut weapon in &mut weapons {
+        weapon.ready_in = (weapon.ready_in - time.delta_seconds()).max(0.0);
+    }
+}
+
+/// Opens a hitbox for every attack asked for with a weapon off cooldown.
+pub(super) fn start_attacks(
+    mut commands: Commands,
+    mut weapons: Query<(Entity, &mut MeleeAttack, &mut MovementIntent)>,
+) {
+    for (entity, mut weapon, mut intent) in &mut weapons {
+        if !std::mem::take(&mut intent.attack) || weapon.ready_in > 0.0 {
+            continue;
+        }
+        weapon.ready_in = weapon.cooldown;
+
+        let hitbox = (
+            Hitbox {
+                owner: entity,
+                damage: weapon.damage,
+                knockback: weapon.knockback,
+                remaining: weapon.active_time,
+                hit: Vec::new(),
+            },
+            Sensor,
+            Collider::cuboid(weapon.size.x, weapon.size.y, weapon.size.z),
+            // Characters look along -Z, as their movement does
+            TransformBundle::from_transform(Transform::from_xyz(0.0, 0.0, -weapon.reach)),
+        );
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn(hitbox);
+        });
+    }
+}
+
+/// Damages and knocks back everything that entered a hitbox.
+pub(super) fn resolve_hits(
+    mut damage_event_writer: EventWriter<Damage>,
+    mut hitboxes: Query<(&mut Hitbox, &CollidingEntities)>,
+    collider_parents: Query<&ColliderParent>,
+    transforms: Query<&GlobalTransform>,
+    mut targets: Query<Option<&mut LinearVelocity>, With<Health>>,
+) {
+    for (mut hitbox, colliding_entities) in &mut hitboxes {
+        for &collider in colliding_entities.iter() {
+            let target = collider_parents
+                .get(collider)
+                .map_or(collider, |parent| parent.get());
+            if target == hitbox.owner || hitbox.hit.contains(&target) {
+                continue;
+            }
+            let Ok(velocity) = targets.get_mut(target) else {
+                continue;
+            };
+            hitbox.hit.push(target);
+
+            damage_event_writer.send(Damage {
+                target,
+                amount: hitbox.damage,
+            });
+
+            let (Some(mut velocity), Ok(owner), Ok(victim)) = (
+                velocity,
+                transforms.get(hitbox.owner),
+                transforms.get(target),
+            ) else {
+                continue;
+            };
+            let direction = (victim.translation() - owner.translation()) * Vec3::new(1.0, 0.0, 1.0);
+            velocity.0 += direction.normalize_or_zero() * hitbox.knockback;
+        }
+    }
+}
+
+pub(super) fn expire_hitboxes(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut hitboxes: Query<(Entity, &mut Hitbox)>,
+) {
+    for (entity, mut hitbox) in &mut hitboxes {
+        hitbox.remaining -= time.delta_seconds();
+        if hitbox.remaining <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat::CombatPlugin;
+    use crate::health::HealthPlugin;
+    use crate::testing;
+
+    fn app() -> App {
+        let mut app = testing::app();
+        app.add_plugins((HealthPlugin, CombatPlugin));
+        app
+    }
+
+    fn attack(app: &mut App, entity: Entity) {
+        app.world.get_mut::<MovementIntent>(entity).unwrap().attack = true;
+        app.update();
+    }
+
+    fn hitboxes(app: &mut App) -> Vec<Entity> {
+        app.world
+            .query_filtered::<Entity, With<Hitbox>>()
+            .iter(&app.world)
+            .collect()
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let mut app = app();
+        let attacker = app
+            .world
+            .spawn((MeleeAttack::new(10.0, 1.0), MovementIntent::default()))
+            .id();
+
+        attack(&mut app, attacker);
+        assert_eq!(hitboxes(&mut app).len(), 1);
+
+        attack(&mut app, attacker);
+        assert_eq!(hitboxes(&mut app).len(), 1, "attacked during cooldown");
+
+        app.update();
+        assert!(
+            hitboxes(&mut app).is_empty(),
+            "hitbox outlived its active time"
+        );
+
+        for _ in 0..4 {
+            app.update();
+        }
+
+        attack(&mut app, attacker);
+        assert_eq!(hitboxes(&mut app).len(), 1);
+    }
+
+    #[test]
+    fn test_hit() {
+        let mut app = app();
+        let attacker = app
+            .world
+            .spawn((
+                MeleeAttack::new(10.0, 1.0),
+                MovementIntent::default(),
+                Health::new(30.0),
+                LinearVelocity(Vec3::ZERO),
+                GlobalTransform::default(),
+            ))
+            .id();
+        let target = app
+            .world
+            .spawn((
+                Health::new(30.0),
+                LinearVelocity(Vec3::ZERO),
+                GlobalTransform::from_xyz(0.0, 0.0, -1.0),
+            ))
+            .id();
+
+        attack(&mut app, attacker);
+        let hitbox = hitboxes(&mut app)[0];
+        app.world
+            .entity_mut(hitbox)
+            .insert(CollidingEntities([attacker, target].into_iter().collect()));
+        app.update();
+        app.update();
+
+        let health = |app: &App, entity| app.world.get::<Health>(entity).unwrap().current;
+        let velocity = |app: &App, entity| app.world.get::<LinearVelocity>(entity).unwrap().0;
+        assert_eq!(health(&app, target), 20.0, "hit more than once per swing");
+        assert_eq!(health(&app, attacker), 30.0);
+        assert!(velocity(&app, target).z < 0.0);
+        assert_eq!(velocity(&app, attacker), Vec3::ZERO);
+    }
+}
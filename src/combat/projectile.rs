@@ -0,0 +1,326 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+
+use crate::character::MovementIntent;
+use crate::health::{Damage, Health};
+
+/// Height above the shooter's origin at which projectiles are launched.
+const LAUNCH_HEIGHT: f32 = 0.5;
+/// Distance in front of the shooter at which projectiles are launched,
+/// so that they do not start inside its collider.
+const LAUNCH_DISTANCE: f32 = 0.7;
+/// Horizontal speed given to anything a projectile hits.
+const KNOCKBACK: f32 = 4.0;
+
+/// A ranged weapon: on [`MovementKind::Shoot`] it launches a projectile.
+///
+/// [`MovementKind::Shoot`]: crate::character::MovementKind::Shoot
+#[derive(Component)]
+pub struct RangedAttack {
+    pub damage: f32,
+    pub speed: f32,
+    /// How many seconds must pass between two shots.
+    pub cooldown: f32,
+    /// How many seconds a projectile flies before it disappears.
+    pub lifetime: f32,
+    pub radius: f32,
+    /// How strongly gravity pulls the projectile:
+    /// zero for arrows and spells, one for thrown items.
+    pub gravity_scale: f32,
+    ready_in: f32,
+}
+
+impl RangedAttack {
+    pub fn new(damage: f32, speed: f32) -> Self {
+        Self {
+            damage,
+            speed,
+            cooldown: 1.0,
+            lifetime: 3.0,
+            radius: 0.15,
+            gravity_scale: 0.0,
+            ready_in: 0.0,
+        }
+    }
+
+    /// Makes the projectile an item thrown in an arc.
+    pub fn thrown(mut self) -> Self {
+        self.gravity_scale = 1.0;
+        self
+    }
+}
+
+/// A projectile in flight.
+#[derive(Component)]
+pub struct Projectile {
+    owner: Entity,
+    damage: f32,
+    remaining: f32,
+}
+
+/// Projectiles that have hit something or expired, hidden and kept
+/// to be launched again instead of being despawned.
+#[derive(Resource, Default)]
+pub struct ProjectilePool {
+    free: Vec<Entity>,
+    visuals: Option<(Handle<Mesh>, Handle<StandardMaterial>)>,
+}
+
+impl ProjectilePool {
+    fn release(&mut self, commands: &mut Commands, entity: Entity) {
+        commands
+            .entity(entity)
+            .remove::<(Projectile, RigidBody, Collider)>()
+            .insert(Visibility::Hidden);
+        self.free.push(entity);
+    }
+}
+
+pub(super) fn tick_cooldowns(time: Res<Time>, mut weapons: Query<&mut RangedAttack>) {
+    for mut weapon in &mut weapons {
+        weapon.ready_in = (weapon.ready_in - time.delta_seconds()).max(0.0);
+    }
+}
+
+/// Launches a projectile for every shot asked for with a weapon off cooldown.
+pub(super) fn launch(
+    mut commands: Commands,
+    mut weapons: Query<(
+        Entity,
+        &mut RangedAttack,
+        &GlobalTransform,
+        &mut MovementIntent,
+    )>,
+    mut pool: ResMut<ProjectilePool>,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+) {
+    if pool.visuals.is_none() {
+        if let (Some(mut meshes), Some(mut materials)) = (meshes, materials) {
+            pool.visuals = Some((
+                meshes.add(Mesh::from(shape::UVSphere {
+                    radius: 1.0,
+                    ..default()
+                })),
+                materials.add(Color::ORANGE_RED.into()),
+            ));
+        }
+    }
+
+    for (shooter, mut weapon, transform, mut intent) in &mut weapons {
+        let Some(direction) = intent.shoot.take() else {
+            continue;
+        };
+        if weapon.ready_in > 0.0 {
+            continue;
+        }
+        weapon.ready_in = weapon.cooldown;
+
+        // Like movement, the direction is given relative to the shooter
+        let (_, rotation, translation) = transform.to_scale_rotation_translation();
+        let direction = (rotation * direction).normalize_or_zero();
+        let origin = translation + Vec3::Y * LAUNCH_HEIGHT + direction * LAUNCH_DISTANCE;
+
+        let entity = pool.free.pop().unwrap_or_else(|| {
+            let mut projectile = commands.spawn_empty();
+            if let Some((mesh, material)) = pool.visuals.clone() {
+                projectile.insert(PbrBundle {
+                    mesh,
+                    material,
+                    ..default()
+                });
+            }
+            projectile.id()
+        });
+        commands.entity(entity).insert((
+            Projectile {
+                owner: shooter,
+                damage: weapon.damage,
+                remaining: weapon.lifetime,
+            },
+            RigidBody::Dynamic,
+            Sensor,
+            Collider::ball(1.0),
+            // A pooled projectile may still remember what it touched last time
+            CollidingEntities::default(),
+            GravityScale(weapon.gravity_scale),
+            Position(origin),
+            LinearVelocity(direction * weapon.speed),
+            Transform::from_translation(origin).with_scale(Vec3::splat(weapon.radius)),
+            Visibility::Visible,
+        ));
+    }
+}
+
+/// Damages whatever a projectile touched and returns it to the pool.
+/// Anything solid stops a projectile: characters, walls, props and the floor.
+pub(super) fn resolve_hits(
+    mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+    mut damage_event_writer: EventWriter<Damage>,
+    projectiles: Query<(Entity, &Projectile, &LinearVelocity, &CollidingEntities)>,
+    collider_parents: Query<&ColliderParent>,
+    sensors: Query<(), With<Sensor>>,
+    mut targets: Query<Option<&mut LinearVelocity>, (With<Health>, Without<Projectile>)>,
+) {
+    for (entity, projectile, velocity, colliding_entities) in &projectiles {
+        let mut stopped = false;
+        for &collider in colliding_entities.iter() {
+            if sensors.contains(collider) {
+                continue;
+            }
+            let target = collider_parents
+                .get(collider)
+                .map_or(collider, |parent| parent.get());
+            if target == projectile.owner {
+                continue;
+            }
+            stopped = true;
+
+            if let Ok(target_velocity) = targets.get_mut(target) {
+                damage_event_writer.send(Damage {
+                    target,
+                    amount: projectile.damage,
+                });
+                if let Some(mut target_velocity) = target_velocity {
+                    let direction = velocity.0 * Vec3::new(1.0, 0.0, 1.0);
+                    target_velocity.0 += direction.normalize_or_zero() * KNOCKBACK;
+                }
+            }
+            break;
+        }
+
+        if stopped {
+            pool.release(&mut commands, entity);
+        }
+    }
+}
+
+pub(super) fn expire_projectiles(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+    mut projectiles: Query<(Entity, &mut Projectile)>,
+) {
+    for (entity, mut projectile) in &mut projectiles {
+        projectile.remaining -= time.delta_seconds();
+        if projectile.remaining <= 0.0 {
+            pool.release(&mut commands, entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat::CombatPlugin;
+    use crate::health::HealthPlugin;
+    use crate::testing;
+
+    fn app() -> App {
+        let mut app = testing::app();
+        app.add_plugins((HealthPlugin, CombatPlugin));
+        app
+    }
+
+    fn shoot(app: &mut App, entity: Entity) {
+        app.world.get_mut::<MovementIntent>(entity).unwrap().shoot = Some(Vec3::NEG_Z);
+        app.update();
+    }
+
+    fn projectiles(app: &mut App) -> Vec<Entity> {
+        app.world
+            .query_filtered::<Entity, With<Projectile>>()
+            .iter(&app.world)
+            .collect()
+    }
+
+    #[test]
+    fn test_launch_and_pool() {
+        let mut app = app();
+        let shooter = app
+            .world
+            .spawn((
+                RangedAttack::new(5.0, 10.0),
+                MovementIntent::default(),
+                GlobalTransform::from(
+                    Transform::from_xyz(1.0, 0.0, 0.0)
+                        .with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)),
+                ),
+            ))
+            .id();
+
+        shoot(&mut app, shooter);
+        let launched = projectiles(&mut app);
+        assert_eq!(launched.len(), 1);
+        let velocity = app.world.get::<LinearVelocity>(launched[0]).unwrap().0;
+        assert!(velocity.distance(Vec3::NEG_X * 10.0) < 1e-4);
+
+        shoot(&mut app, shooter);
+        assert_eq!(projectiles(&mut app).len(), 1, "shot during cooldown");
+
+        for _ in 0..30 {
+            app.update();
+        }
+        assert!(
+            projectiles(&mut app).is_empty(),
+            "projectile outlived its lifetime"
+        );
+        assert_eq!(app.world.resource::<ProjectilePool>().free, launched);
+
+        shoot(&mut app, shooter);
+        assert_eq!(
+            projectiles(&mut app),
+            launched,
+            "pooled projectile was not reused"
+        );
+    }
+
+    #[test]
+    fn test_hit() {
+        let mut app = app();
+        let shooter = app
+            .world
+            .spawn((
+                RangedAttack::new(5.0, 10.0),
+                MovementIntent::default(),
+                GlobalTransform::default(),
+            ))
+            .id();
+        let target = app
+            .world
+            .spawn((
+                Health::new(20.0),
+                LinearVelocity(Vec3::ZERO),
+                GlobalTransform::from_xyz(0.0, 0.0, -3.0),
+            ))
+            .id();
+        let wall = app.world.spawn_empty().id();
+
+        shoot(&mut app, shooter);
+        let projectile = projectiles(&mut app)[0];
+        app.world
+            .entity_mut(projectile)
+            .insert(CollidingEntities([shooter, target].into_iter().collect()));
+        app.update();
+        app.update();
+
+        assert_eq!(app.world.get::<Health>(target).unwrap().current, 15.0);
+        assert!(app.world.get::<LinearVelocity>(target).unwrap().z < 0.0);
+        assert!(projectiles(&mut app).is_empty());
+
+        for _ in 0..10 {
+            app.update();
+        }
+        shoot(&mut app, shooter);
+        let projectile = projectiles(&mut app)[0];
+        app.world
+            .entity_mut(projectile)
+            .insert(CollidingEntities([wall].into_iter().collect()));
+        app.update();
+        assert!(
+            projectiles(&mut app).is_empty(),
+            "walls do not stop projectiles"
+        );
+    }
+}
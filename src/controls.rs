@@ -0,0 +1,622 @@
+//! Rebindable controls: an [`ActionMap`] from game actions to keys, mouse
+//! buttons and gamepad buttons and axes. The shipped bindings are loaded from
+//! a RON file, and bindings changed in game are saved next to the game and
+//! laid over the shipped ones action by action.
+//!
+//! Without a settings screen, [`Action::Rebind`] rebinds from the game itself:
+//! the next pressed input picks the action, the one after it is its new binding.
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The shipped bindings, relative to the assets folder.
+pub const CONTROLS_PATH: &str = "data/controls.ron";
+/// The shipped bindings as they were at build time, for when the assets are broken.
+const BUILTIN_CONTROLS: &str = include_str!("../assets/data/controls.ron");
+/// The player's own bindings, relative to the game folder.
+const USER_CONTROLS_PATH: &str = "controls.ron";
+/// How far an axis has to be pushed to be taken as a new binding.
+const REBIND_AXIS_THRESHOLD: f32 = 0.5;
+/// The key that cancels a rebinding. It cannot be bound to an action.
+pub const CANCEL_KEY: KeyCode = KeyCode::Escape;
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        let shipped = ActionMap::load(CONTROLS_PATH).unwrap_or_else(|error| {
+            warn!("falling back to the built-in controls: {}", error);
+            ActionMap::builtin()
+        });
+        let file = ControlsFile::default();
+        let map = match file.0.exists().then(|| ActionMap::read(&file.0)) {
+            | Some(Ok(user)) => shipped.merge(user),
+            | Some(Err(error)) => {
+                warn!("falling back to the default controls: {}", error);
+                shipped
+            }
+            | None => shipped,
+        };
+
+        app.insert_resource(map)
+            .insert_resource(file)
+            .init_resource::<Rebinding>()
+            .init_resource::<PickingRebind>()
+            .add_event::<StartRebind>()
+            .add_event::<RebindResult>()
+            .add_systems(Update, (start_rebind, capture_rebind, pick_rebind).chain());
+    }
+}
+
+/// Something the player can do, bound to one or more inputs in the [`ActionMap`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    RotateLeft,
+    RotateRight,
+    /// Tilts the camera, for sticks; the mouse tilts it directly.
+    LookUp,
+    LookDown,
+    Jump,
+    Attack,
+    Shoot,
+    Interact,
+    Sprint,
+    Dash,
+    Dodge,
+    /// Switches the camera to its next mode.
+    CycleCamera,
+    /// Grabs the cursor for mouse look.
+    GrabCursor,
+    /// Gives a grabbed cursor back.
+    ReleaseCursor,
+    /// Moves a free-flying camera.
+    FlyForward,
+    FlyBack,
+    FlyLeft,
+    FlyRight,
+    FlyUp,
+    FlyDown,
+    /// Rebinds the action of the next pressed input to the input after it.
+    Rebind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+/// A single input an [`Action`] can be bound to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Button(GamepadButtonType),
+    /// One direction of a stick or trigger axis, read as an analog value.
+    Axis(GamepadAxisType, AxisDirection),
+}
+
+/// Which inputs each action is bound to.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ActionMap {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+    #[serde(default)]
+    pub axis_response: AxisResponse,
+}
+
+/// How a stick or trigger position turns into an action value.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct AxisResponse {
+    /// Positions closer to the rest than this count as zero,
+    /// so that a worn stick does not drift.
+    pub deadzone: f32,
+    /// The exponent of the response curve: 1 is linear, larger values
+    /// give finer control near the rest.
+    pub curve: f32,
+}
+
+impl Default for AxisResponse {
+    fn default() -> Self {
+        AxisResponse {
+            deadzone: 0.15,
+            curve: 1.0,
+        }
+    }
+}
+
+impl AxisResponse {
+    /// Maps an axis position from 0 to 1 past the deadzone onto the curve.
+    pub fn apply(&self, value: f32) -> f32 {
+        if value <= self.deadzone {
+            return 0.0;
+        }
+        ((value - self.deadzone) / (1.0 - self.deadzone))
+            .clamp(0.0, 1.0)
+            .powf(self.curve)
+    }
+}
+
+impl ActionMap {
+    /// Loads bindings from `path` in the assets folder.
+    pub fn load(path: &str) -> Result<Self, String> {
+        Self::read(&FileAssetReader::get_base_path().join("assets").join(path))
+    }
+
+    /// The bindings shipped with this build of the game.
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_CONTROLS).expect("the built-in controls are valid")
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        Self::parse(&source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let map: Self = ron::from_str(source).map_err(|error| error.to_string())?;
+        for (&action, bindings) in map.bindings.iter() {
+            for &binding in bindings {
+                if binding == Binding::Key(CANCEL_KEY) {
+                    return Err(format!(
+                        "{:?} is reserved and cannot be bound to {:?}",
+                        binding, action
+                    ));
+                }
+                if let Some(other) = map.conflict(action, binding) {
+                    return Err(format!(
+                        "{:?} is bound to both {:?} and {:?}",
+                        binding, action, other
+                    ));
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn store(&self, path: &Path) -> Result<(), String> {
+        let source = ron::ser::to_string_pretty(self, PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+        std::fs::write(path, source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    /// Lays the player's bindings over these action by action, so that actions
+    /// added after `user` was saved keep their own bindings. Those of them
+    /// the player has given to another action are dropped.
+    pub fn merge(mut self, user: ActionMap) -> Self {
+        for bindings in self.bindings.values_mut() {
+            bindings.retain(|binding| !user.bindings.values().any(|taken| taken.contains(binding)));
+        }
+        self.bindings.extend(user.bindings);
+        self.axis_response = user.axis_response;
+        self
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// The action bound to `binding` and the number of the binding among its others.
+    pub fn action(&self, binding: Binding) -> Option<(Action, usize)> {
+        self.bindings.iter().find_map(|(&action, bindings)| {
+            Some((action, bindings.iter().position(|&other| other == binding)?))
+        })
+    }
+
+    /// Another action already bound to `binding`, if any.
+    pub fn conflict(&self, action: Action, binding: Binding) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(&other, bindings)| other != action && bindings.contains(&binding))
+            .map(|(&other, _)| other)
+    }
+
+    /// Binds `action` to `binding` in place of its binding number `slot`,
+    /// or in addition to the others if there is no such slot. Fails with
+    /// the conflicting action if `binding` is already taken.
+    pub fn bind(&mut self, action: Action, slot: usize, binding: Binding) -> Result<(), Action> {
+        if let Some(other) = self.conflict(action, binding) {
+            return Err(other);
+        }
+        let bindings = self.bindings.entry(action).or_default();
+        if bindings.contains(&binding) {
+            return Ok(());
+        }
+        match bindings.get_mut(slot) {
+            | Some(old) => *old = binding,
+            | None => bindings.push(binding),
+        }
+        Ok(())
+    }
+}
+
+/// Where bindings changed in game are saved.
+#[derive(Resource)]
+pub struct ControlsFile(pub PathBuf);
+
+impl Default for ControlsFile {
+    fn default() -> Self {
+        ControlsFile(FileAssetReader::get_base_path().join(USER_CONTROLS_PATH))
+    }
+}
+
+/// Reads actions from the inputs they are bound to.
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    pub map: Res<'w, ActionMap>,
+    keys: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+    buttons: Res<'w, Input<GamepadButton>>,
+}
+
+impl ActionInput<'_> {
+    /// How strongly the keyboard and mouse hold `action`, from 0 to 1.
+    pub fn keyboard_value(&self, action: Action) -> f32 {
+        self.value(action, None)
+    }
+
+    pub fn keyboard_just_pressed(&self, action: Action) -> bool {
+        self.just_pressed(action, None)
+    }
+
+    /// How strongly `gamepad` holds `action`, from 0 to 1.
+    pub fn gamepad_value(&self, gamepad: Gamepad, action: Action) -> f32 {
+        self.value(action, Some(gamepad))
+    }
+
+    /// Axes are analog and never count as just pressed.
+    pub fn gamepad_just_pressed(&self, gamepad: Gamepad, action: Action) -> bool {
+        self.just_pressed(action, Some(gamepad))
+    }
+
+    fn value(&self, action: Action, gamepad: Option<Gamepad>) -> f32 {
+        self.map
+            .bindings(action)
+            .iter()
+            .map(|&binding| match (binding, gamepad) {
+                | (Binding::Key(key), None) => self.keys.pressed(key) as u8 as f32,
+                | (Binding::Mouse(button), None) => self.mouse.pressed(button) as u8 as f32,
+                | (Binding::Button(button_type), Some(gamepad)) => self
+                    .buttons
+                    .pressed(GamepadButton::new(gamepad, button_type))
+                    as u8 as f32,
+                | (Binding::Axis(axis_type, direction), Some(gamepad)) => {
+                    let value = self
+                        .axes
+                        .get(GamepadAxis::new(gamepad, axis_type))
+                        .unwrap_or(0.0);
+                    self.map.axis_response.apply(match direction {
+                        | AxisDirection::Positive => value,
+                        | AxisDirection::Negative => -value,
+                    })
+                }
+                | _ => 0.0,
+            })
+            .fold(0.0, f32::max)
+    }
+
+    fn just_pressed(&self, action: Action, gamepad: Option<Gamepad>) -> bool {
+        self.map
+            .bindings(action)
+            .iter()
+            .any(|&binding| match (binding, gamepad) {
+                | (Binding::Key(key), None) => self.keys.just_pressed(key),
+                | (Binding::Mouse(button), None) => self.mouse.just_pressed(button),
+                | (Binding::Button(button_type), Some(gamepad)) => self
+                    .buttons
+                    .just_pressed(GamepadButton::new(gamepad, button_type)),
+                | _ => false,
+            })
+    }
+}
+
+/// Sent by the settings screen or after [`Action::Rebind`] to bind the next
+/// pressed input to `action` in place of its binding number `slot`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct StartRebind {
+    pub action: Action,
+    pub slot: usize,
+}
+
+/// The outcome of a [`StartRebind`], for the settings screen to show.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub enum RebindResult {
+    Bound(Action, Binding),
+    /// The input is already bound to another action, nothing changed.
+    Conflict {
+        action: Action,
+        binding: Binding,
+        with: Action,
+    },
+    /// [`CANCEL_KEY`] was pressed.
+    Cancelled(Action),
+}
+
+/// The rebinding in progress, waiting for an input.
+#[derive(Resource, Default)]
+pub struct Rebinding(pub Option<StartRebind>);
+
+/// Whether [`Action::Rebind`] was pressed and the next input picks
+/// the action to rebind.
+#[derive(Resource, Default)]
+pub struct PickingRebind(pub bool);
+
+/// The inputs that can be bound, to take the first one pressed.
+#[derive(SystemParam)]
+struct PressedInput<'w> {
+    keys: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+    gamepads: Res<'w, Gamepads>,
+}
+
+impl PressedInput<'_> {
+    /// The first input just pressed on any device, or an axis pushed far enough.
+    fn first(&self) -> Option<Binding> {
+        let axis = || {
+            self.gamepads.iter().find_map(|gamepad| {
+                AXES.iter().find_map(|&axis_type| {
+                    let value = self.axes.get(GamepadAxis::new(gamepad, axis_type))?;
+                    let direction = if value >= REBIND_AXIS_THRESHOLD {
+                        AxisDirection::Positive
+                    } else if value <= -REBIND_AXIS_THRESHOLD {
+                        AxisDirection::Negative
+                    } else {
+                        return None;
+                    };
+                    Some(Binding::Axis(axis_type, direction))
+                })
+            })
+        };
+        self.keys
+            .get_just_pressed()
+            .next()
+            .map(|&key| Binding::Key(key))
+            .or_else(|| {
+                self.mouse
+                    .get_just_pressed()
+                    .next()
+                    .map(|&button| Binding::Mouse(button))
+            })
+            .or_else(|| {
+                self.buttons
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Button(button.button_type))
+            })
+            .or_else(axis)
+    }
+}
+
+fn start_rebind(mut events: EventReader<StartRebind>, mut rebinding: ResMut<Rebinding>) {
+    if let Some(&event) = events.read().last() {
+        rebinding.0 = Some(event);
+    }
+}
+
+/// Binds the first input pressed during a rebinding and saves the controls.
+fn capture_rebind(
+    mut rebinding: ResMut<Rebinding>,
+    mut map: ResMut<ActionMap>,
+    mut results: EventWriter<RebindResult>,
+    file: Res<ControlsFile>,
+    input: PressedInput,
+) {
+    let Some(StartRebind { action, slot }) = rebinding.0 else {
+        return;
+    };
+    if input.keys.just_pressed(CANCEL_KEY) {
+        rebinding.0 = None;
+        results.send(RebindResult::Cancelled(action));
+        return;
+    }
+    let Some(binding) = input.first() else {
+        return;
+    };
+
+    rebinding.0 = None;
+    match map.bind(action, slot, binding) {
+        | Ok(()) => {
+            if let Err(error) = map.store(&file.0) {
+                warn!("could not save the controls: {}", error);
+            }
+            results.send(RebindResult::Bound(action, binding));
+        }
+        | Err(with) => {
+            results.send(RebindResult::Conflict {
+                action,
+                binding,
+                with,
+            });
+        }
+    }
+}
+
+/// Starts a rebinding from [`Action::Rebind`] in place of the binding pressed
+/// after it. Runs after the capture, so that the picking input is not taken
+/// as the new binding in the same frame.
+fn pick_rebind(
+    mut picking: ResMut<PickingRebind>,
+    mut starts: EventWriter<StartRebind>,
+    rebinding: Res<Rebinding>,
+    map: Res<ActionMap>,
+    input: PressedInput,
+) {
+    if rebinding.0.is_some() {
+        return;
+    }
+    if !picking.0 {
+        let rebind = map.bindings(Action::Rebind);
+        picking.0 = input
+            .first()
+            .is_some_and(|binding| rebind.contains(&binding));
+        return;
+    }
+    if input.keys.just_pressed(CANCEL_KEY) {
+        picking.0 = false;
+        return;
+    }
+    // An input bound to nothing picks nothing
+    if let Some((action, slot)) = input.first().and_then(|binding| map.action(binding)) {
+        picking.0 = false;
+        starts.send(StartRebind { action, slot });
+    }
+}
+
+/// Axes a rebinding listens to.
+const AXES: [GamepadAxisType; 6] = [
+    GamepadAxisType::LeftStickX,
+    GamepadAxisType::LeftStickY,
+    GamepadAxisType::LeftZ,
+    GamepadAxisType::RightStickX,
+    GamepadAxisType::RightStickY,
+    GamepadAxisType::RightZ,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_shipped_controls() {
+        let map = ActionMap::load(CONTROLS_PATH).unwrap();
+        assert_eq!(map.bindings(Action::Jump)[0], Binding::Key(KeyCode::Space));
+        assert!(map
+            .bindings(Action::Shoot)
+            .iter()
+            .any(|binding| matches!(binding, Binding::Key(_) | Binding::Mouse(_))));
+
+        let conflicting = "(bindings: { Jump: [Key(Space)], Attack: [Key(Space)] })";
+        assert!(ActionMap::parse(conflicting).is_err());
+        let reserved = "(bindings: { ReleaseCursor: [Key(Escape)] })";
+        assert!(ActionMap::parse(reserved).is_err());
+    }
+
+    #[test]
+    fn test_axis_response() {
+        let response = AxisResponse {
+            deadzone: 0.2,
+            curve: 2.0,
+        };
+        assert_eq!(response.apply(0.1), 0.0);
+        assert_eq!(response.apply(-0.5), 0.0);
+        assert!((response.apply(0.6) - 0.25).abs() < 1e-5);
+        assert_eq!(response.apply(1.0), 1.0);
+    }
+
+    #[test]
+    fn test_builtin_controls() {
+        assert_eq!(
+            ActionMap::builtin(),
+            ActionMap::load(CONTROLS_PATH).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let user = ActionMap::parse("(bindings: { Jump: [Key(W)], Attack: [Key(K)] })").unwrap();
+        let map = ActionMap::builtin().merge(user);
+        assert_eq!(map.bindings(Action::Jump), [Binding::Key(KeyCode::W)]);
+        assert_eq!(map.bindings(Action::Attack), [Binding::Key(KeyCode::K)]);
+        // Actions missing from the player's file keep what the player left them
+        assert_eq!(
+            map.bindings(Action::MoveForward),
+            [Binding::Axis(
+                GamepadAxisType::LeftStickY,
+                AxisDirection::Positive
+            )]
+        );
+        assert_eq!(map.bindings(Action::Rebind), [Binding::Key(KeyCode::F1)]);
+    }
+
+    /// Rebinding alone, saving the controls to `path`.
+    fn app(path: &Path) -> App {
+        let mut app = testing::app();
+        app.insert_resource(ActionMap::load(CONTROLS_PATH).unwrap())
+            .insert_resource(ControlsFile(path.to_path_buf()))
+            .init_resource::<Rebinding>()
+            .init_resource::<PickingRebind>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<MouseButton>>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .init_resource::<Gamepads>()
+            .add_event::<StartRebind>()
+            .add_event::<RebindResult>()
+            .add_systems(Update, (start_rebind, capture_rebind, pick_rebind).chain());
+        app
+    }
+
+    /// Holds `key` for one frame and returns the rebinding outcome of that frame.
+    fn tap(app: &mut App, key: KeyCode) -> Option<RebindResult> {
+        let mut keys = app.world.resource_mut::<Input<KeyCode>>();
+        keys.clear();
+        keys.press(key);
+        app.update();
+        let mut keys = app.world.resource_mut::<Input<KeyCode>>();
+        keys.release(key);
+        keys.clear();
+        let events = app.world.resource::<Events<RebindResult>>();
+        events.iter_current_update_events().last().copied()
+    }
+
+    #[test]
+    fn test_rebind() {
+        let path = std::env::temp_dir().join("cult_of_eat_test_controls.ron");
+        let mut app = app(&path);
+        let rebind = |app: &mut App, action, key| {
+            app.world.send_event(StartRebind { action, slot: 0 });
+            app.update();
+            tap(app, key)
+        };
+
+        assert_eq!(
+            rebind(&mut app, Action::Jump, KeyCode::F),
+            Some(RebindResult::Conflict {
+                action: Action::Jump,
+                binding: Binding::Key(KeyCode::F),
+                with: Action::Attack,
+            })
+        );
+        assert_eq!(
+            rebind(&mut app, Action::Jump, KeyCode::J),
+            Some(RebindResult::Bound(Action::Jump, Binding::Key(KeyCode::J)))
+        );
+        let map = app.world.resource::<ActionMap>();
+        assert_eq!(map.bindings(Action::Jump)[0], Binding::Key(KeyCode::J));
+        assert_eq!(ActionMap::read(&path).as_ref(), Ok(map));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rebind_action() {
+        let path = std::env::temp_dir().join("cult_of_eat_test_rebind_action.ron");
+        let mut app = app(&path);
+
+        assert_eq!(tap(&mut app, KeyCode::F1), None);
+        assert_eq!(tap(&mut app, KeyCode::F), None);
+        assert_eq!(
+            tap(&mut app, KeyCode::K),
+            Some(RebindResult::Bound(
+                Action::Attack,
+                Binding::Key(KeyCode::K)
+            ))
+        );
+        let map = app.world.resource::<ActionMap>();
+        assert_eq!(map.bindings(Action::Attack)[0], Binding::Key(KeyCode::K));
+        assert!(!app.world.resource::<PickingRebind>().0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
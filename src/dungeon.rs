@@ -1,17 +1,37 @@
 //! Модуль предназначенный для генерации данжена
 
+mod ai;
+mod archetype;
 mod commands;
 mod components;
-mod enums;
-mod level;
+mod cutaway;
+mod encounter;
+pub(crate) mod enums;
+mod fog;
+pub(crate) mod level;
+mod replay;
+mod save;
 
 use crate::prelude::*;
 
-use commands::{SpawnDoor, SpawnFloor, SpawnPlayer, SpawnWall};
-use enums::TileType;
-use level::Level;
+use ai::{patrol_route, EnemyAiPlugin};
+use archetype::{EnemyCatalog, ENEMIES_PATH};
+use commands::{SpawnDoor, SpawnFloor, SpawnPlayer, SpawnProp, SpawnRoomFeature, SpawnWall};
+use components::{LootDrop, Pit, Player, RoomFeature, Tile};
+use cutaway::CutawayPlugin;
+use encounter::plan_encounters;
+use enums::{MarkerType, RoomRole, TileType};
+use fog::FogOfWarPlugin;
+use level::pathfinding::NavGrid;
+use level::{Level, LevelSettings};
+use save::{SaveFile, SavePlugin};
+
+pub use replay::{ReplayMode, ReplayPlugin};
+pub use save::StartMode;
 
 use bevy::pbr::DirectionalLightShadowMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::f32::consts::PI;
 
 use self::commands::SpawnEnemy;
@@ -19,13 +39,39 @@ use self::commands::SpawnEnemy;
 pub const DUNGEON_ROW: usize = 15;
 pub const DUNGEON_COLUMN: usize = 15;
 
-pub struct DungeonPlugin;
+/// Как близко, в метрах, нужно подойти к сундуку или алтарю, чтобы им воспользоваться.
+const INTERACT_RANGE: f32 = 2.5;
+/// Сколько игроков может играть за одним экраном.
+const MAX_PLAYERS: usize = 4;
+
+pub type DungeonNavGrid = NavGrid<DUNGEON_ROW, DUNGEON_COLUMN>;
+
+/// Подземелье. Сохраненная игра продолжается, только если так выбрано
+/// в [`StartMode`], иначе начинается новая.
+pub struct DungeonPlugin(pub StartMode);
 
 impl Plugin for DungeonPlugin {
     fn build(&self, app: &mut App) {
+        // Без каталога уровень строится без врагов
+        let catalog = EnemyCatalog::load(ENEMIES_PATH).unwrap_or_else(|error| {
+            warn!("{}", error);
+            EnemyCatalog::default()
+        });
+
         app.insert_resource(DirectionalLightShadowMap { size: 512 })
+            .add_plugins((EnemyAiPlugin, FogOfWarPlugin, CutawayPlugin, SavePlugin))
+            .insert_resource(self.0)
+            .init_resource::<LevelSettings>()
+            .init_resource::<SaveFile>()
+            .insert_resource(catalog)
             .add_systems(Startup, setup)
-            .add_systems(Update, gizmos_system);
+            .add_systems(Update, gizmos_system)
+            .add_systems(
+                FixedUpdate,
+                fall_into_pits.after(ControllerStep).before(apply_damage),
+            )
+            .add_systems(Update, drop_loot.run_if(resource_exists::<LootRng>()))
+            .add_systems(Update, (use_features, join_players));
     }
 }
 
@@ -33,11 +79,24 @@ fn setup(
     mut commands: Commands,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
+    settings: Res<LevelSettings>,
+    catalog: Res<EnemyCatalog>,
 ) {
+    let level = Level::<DUNGEON_ROW, DUNGEON_COLUMN>::new(&settings);
+    let nav_grid = DungeonNavGrid::from_level(&level);
+    let encounters = plan_encounters(
+        &level.room_layer.rooms,
+        &nav_grid,
+        &catalog,
+        settings.depth,
+        settings.seed,
+    );
+
     let Level {
         room_layer,
         wall_layer,
-    } = Level::<DUNGEON_ROW, DUNGEON_COLUMN>::new();
+        prop_layer,
+    } = level;
 
     for (x, z, tile) in room_layer.layer.iter() {
         commands.add(SpawnFloor::new(x, 0.0, z, *tile));
@@ -55,6 +114,11 @@ fn setup(
         }
     }
 
+    for prop in prop_layer.props.iter() {
+        let (x, z) = prop_layer.layer.get_coordiante(prop.i, prop.j);
+        commands.add(SpawnProp::new(x, 0.0, z, prop.prop_type, prop.anchor));
+    }
+
     let cube_mesh = meshes.add(Mesh::from(shape::Cube { size: 1.0 }));
 
     // Ground
@@ -69,16 +133,38 @@ fn setup(
         Collider::cuboid(1.0, 1.0, 1.0),
     ));
 
-    if let Some(room) = room_layer.rooms.first() {
+    for room in room_layer.rooms.iter() {
         let (i, j) = room.center();
-        commands.add(SpawnPlayer::new((i * 4) as f32, 0.5, (j * 4) as f32));
+        let (x, z) = room_layer.layer.get_coordiante(i as usize, j as usize);
+        match room.role {
+            | RoomRole::Start => commands.add(SpawnPlayer::new(x, 0.5, z)),
+            | RoomRole::Common | RoomRole::Boss => {}
+            | role @ (RoomRole::Treasure | RoomRole::Shop | RoomRole::Shrine) => {
+                commands.add(SpawnRoomFeature::new(x, 0.0, z, role))
+            }
+        }
+
+        for marker in room.markers.iter() {
+            if marker.marker_type == MarkerType::Loot {
+                let (x, z) = room_layer
+                    .layer
+                    .get_coordiante(marker.i as usize, marker.j as usize);
+                commands.add(SpawnRoomFeature::new(x, 0.0, z, RoomRole::Treasure));
+            }
+        }
     }
 
-    for room in room_layer.rooms.iter() {
-        let (i, j) = room.center();
-        commands.add(SpawnEnemy::new((i * 4) as f32, 0.5, (j * 4) as f32));
+    for encounter in encounters {
+        let (x, z) = room_layer
+            .layer
+            .get_coordiante(encounter.cell.0, encounter.cell.1);
+        let patrol = patrol_route(&nav_grid, &room_layer.rooms[encounter.room].cells());
+        commands.add(SpawnEnemy::new(encounter.archetype, x, 0.5, z).patrol(patrol));
     }
 
+    commands.insert_resource(nav_grid);
+    commands.insert_resource(LootRng(StdRng::seed_from_u64(settings.seed)));
+
     // light
     commands.spawn(DirectionalLightBundle {
         directional_light: DirectionalLight {
@@ -95,6 +181,120 @@ fn setup(
     });
 }
 
+/// Генератор добычи. Растет из зерна уровня, чтобы запись игры
+/// воспроизводилась вместе с выпавшими сундуками.
+#[derive(Resource)]
+struct LootRng(StdRng);
+
+/// Оставляет добычу на месте погибших врагов.
+fn drop_loot(
+    mut commands: Commands,
+    mut died_event_reader: EventReader<Died>,
+    mut rng: ResMut<LootRng>,
+    loot: Query<&LootDrop>,
+) {
+    for event in died_event_reader.read() {
+        let Ok(loot) = loot.get(event.entity) else {
+            continue;
+        };
+        for entry in loot.0.iter() {
+            if rng.0.gen_bool(entry.chance) {
+                let position = event.position;
+                commands.add(SpawnRoomFeature::new(
+                    position.x,
+                    0.0,
+                    position.z,
+                    entry.feature,
+                ));
+            }
+        }
+    }
+}
+
+/// Убивает всех, кто ступил в яму.
+fn fall_into_pits(
+    pits: Query<&CollidingEntities, With<Pit>>,
+    collider_parents: Query<&ColliderParent>,
+    bodies: Query<&Health>,
+    mut damage_event_writer: EventWriter<Damage>,
+) {
+    for colliding_entities in &pits {
+        for &collider in colliding_entities.iter() {
+            let target = collider_parents
+                .get(collider)
+                .map_or(collider, |parent| parent.get());
+            if let Ok(health) = bodies.get(target) {
+                damage_event_writer.send(Damage {
+                    target,
+                    amount: health.current,
+                });
+            }
+        }
+    }
+}
+
+/// Добавляет игрока рядом с первым, когда подключен геймпад, которому
+/// не хватило игрока.
+fn join_players(
+    mut commands: Commands,
+    gamepads: Res<Gamepads>,
+    players: Query<(&Transform, &PlayerId, Option<&PlayerGamepad>), With<Player>>,
+) {
+    let owned = players.iter().filter(|(_, _, pad)| pad.is_some()).count();
+    let waiting = players.iter().any(|(_, _, pad)| pad.is_none());
+    if waiting || gamepads.iter().count() <= owned || players.iter().count() >= MAX_PLAYERS {
+        return;
+    }
+    let Some((first, _, _)) = players.iter().min_by_key(|(_, id, _)| **id) else {
+        return;
+    };
+    let id = players.iter().map(|(_, id, _)| id.0 + 1).max().unwrap_or(0);
+    let position = first.translation + Vec3::X;
+    commands.add(SpawnPlayer::new(position.x, position.y, position.z).player(id));
+}
+
+/// Открывает сундук или молится у алтаря рядом с игроком. Прилавок
+/// пока не торгует: платить в игре нечем.
+fn use_features(
+    mut commands: Commands,
+    mut movement_event_reader: EventReader<MovementAction>,
+    mut players: Query<(&Transform, Option<&mut Health>), With<PlayerControlled>>,
+    features: Query<(Entity, &RoomFeature, &Tile)>,
+) {
+    for event in movement_event_reader.read() {
+        if !matches!(event.kind, MovementKind::Interact) {
+            continue;
+        }
+        let Ok((player, health)) = players.get_mut(event.entity) else {
+            continue;
+        };
+        let nearest = features
+            .iter()
+            .filter(|(_, feature, _)| feature.0 != RoomRole::Shop)
+            .map(|(entity, feature, tile)| (entity, feature, tile.0.distance(player.translation)))
+            .filter(|&(_, _, distance)| distance <= INTERACT_RANGE)
+            .min_by(|a, b| a.2.total_cmp(&b.2));
+        let Some((entity, feature, _)) = nearest else {
+            continue;
+        };
+        match feature.0 {
+            | RoomRole::Treasure => {
+                info!("the chest is opened");
+                commands.entity(entity).despawn_recursive();
+            }
+            // Алтарь исцеляет один раз, после этого он просто статуя
+            | RoomRole::Shrine => {
+                info!("the shrine heals");
+                if let Some(mut health) = health {
+                    health.current = health.max;
+                }
+                commands.entity(entity).remove::<RoomFeature>();
+            }
+            | RoomRole::Shop | RoomRole::Common | RoomRole::Start | RoomRole::Boss => {}
+        }
+    }
+}
+
 fn gizmos_system(mut gizmos: Gizmos) {
     for i in 0..DUNGEON_ROW {
         for j in 0..DUNGEON_COLUMN {
@@ -106,3 +306,72 @@ fn gizmos_system(mut gizmos: Gizmos) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_use_features() {
+        let mut app = testing::app();
+        app.add_event::<MovementAction>()
+            .add_systems(Update, use_features);
+        let mut health = Health::new(10.0);
+        health.current = 2.0;
+        let player = app
+            .world
+            .spawn((PlayerControlled, Transform::default(), health))
+            .id();
+        let feature = |app: &mut App, role, x| {
+            app.world
+                .spawn((RoomFeature(role), Tile(Vec3::new(x, 0.0, 0.0))))
+                .id()
+        };
+        let shop = feature(&mut app, RoomRole::Shop, 0.5);
+        let shrine = feature(&mut app, RoomRole::Shrine, 1.0);
+        let interact = |app: &mut App| {
+            app.world.send_event(MovementAction {
+                entity: player,
+                kind: MovementKind::Interact,
+            });
+            app.update();
+        };
+
+        interact(&mut app);
+        assert_eq!(app.world.get::<Health>(player).unwrap().current, 10.0);
+        assert!(
+            app.world.get::<RoomFeature>(shrine).is_none(),
+            "the shrine is used up"
+        );
+        assert!(app.world.get::<RoomFeature>(shop).is_some());
+
+        app.world.get_mut::<Health>(player).unwrap().current = 2.0;
+        interact(&mut app);
+        assert_eq!(app.world.get::<Health>(player).unwrap().current, 2.0);
+    }
+
+    #[test]
+    fn test_fall_into_pits() {
+        let mut app = testing::app();
+        app.add_plugins(HealthPlugin)
+            .add_systems(FixedUpdate, fall_into_pits.before(apply_damage));
+        let body = |app: &mut App| {
+            app.world
+                .spawn((
+                    Health::new(10.0),
+                    OnDeath::Corpse,
+                    GlobalTransform::default(),
+                ))
+                .id()
+        };
+        let fallen = body(&mut app);
+        let standing = body(&mut app);
+        app.world
+            .spawn((Pit, CollidingEntities([fallen].into_iter().collect())));
+
+        app.update();
+        assert!(app.world.get::<Dead>(fallen).is_some());
+        assert!(app.world.get::<Dead>(standing).is_none());
+    }
+}
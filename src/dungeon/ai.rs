@@ -0,0 +1,510 @@
+//! Поведение врагов: конечный автомат поверх карты проходимости уровня.
+
+use crate::prelude::*;
+
+use super::components::{Enemy, Player};
+use super::level::pathfinding::DijkstraMap;
+use super::DungeonNavGrid;
+
+/// Сколько секунд враг стоит на месте между точками обхода.
+const IDLE_TIME: f32 = 2.0;
+/// Пауза между двумя атаками одного врага.
+const ATTACK_COOLDOWN: f32 = 1.0;
+/// Расстояние до центра последней клетки пути, на котором она считается достигнутой.
+const ARRIVE_DISTANCE: f32 = 0.5;
+
+pub struct EnemyAiPlugin;
+
+impl Plugin for EnemyAiPlugin {
+    fn build(&self, app: &mut App) {
+        // Враги решают на тех же фиксированных шагах, на которых движутся
+        app.add_event::<EnemyAttack>().add_systems(
+            FixedUpdate,
+            (think, strike, steer, apply_steering)
+                .chain()
+                .before(ControllerStep)
+                .run_if(resource_exists::<DungeonNavGrid>()),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnemyState {
+    /// Стоит на месте и ждет, прежде чем идти к следующей точке обхода.
+    Idle,
+    /// Идет к очередной точке обхода своей комнаты.
+    Patrol,
+    /// Бежит к игроку или к месту, где видел его в последний раз.
+    Chase,
+    /// Стоит рядом с игроком и бьет его.
+    Attack,
+    /// Убегает от игрока, когда здоровья осталось мало.
+    Flee,
+    /// Возвращается туда, где появился.
+    Return,
+}
+
+/// Мозги врага: текущее состояние и то, как далеко он видит и бьет.
+/// Бьет враг своим оружием, [`MeleeAttack`] или [`RangedAttack`].
+#[derive(Component)]
+pub struct EnemyAi {
+    pub state: EnemyState,
+    pub home: Vec3,
+    pub patrol: Vec<(usize, usize)>,
+    pub sight_range: f32,
+    pub attack_range: f32,
+    /// Доля здоровья, ниже которой враг убегает.
+    pub flee_threshold: f32,
+    target: Option<(usize, usize)>,
+    path: Vec<(usize, usize)>,
+    next_patrol: usize,
+    wait: f32,
+    cooldown: f32,
+}
+
+impl EnemyAi {
+    pub fn new(home: Vec3, patrol: Vec<(usize, usize)>) -> Self {
+        EnemyAi {
+            state: EnemyState::Idle,
+            home,
+            patrol,
+            sight_range: 16.0,
+            attack_range: 1.5,
+            flee_threshold: 0.25,
+            target: None,
+            path: Vec::new(),
+            next_patrol: 0,
+            wait: 0.0,
+            cooldown: 0.0,
+        }
+    }
+
+    pub fn with_flee_threshold(mut self, flee_threshold: f32) -> Self {
+        self.flee_threshold = flee_threshold;
+        self
+    }
+
+    /// Следующая точка на пути к цели. Путь перестраивается, когда сменилась
+    /// цель или враг с него сошел, а недостижимая цель сбрасывается.
+    fn next_waypoint(
+        &mut self,
+        grid: &DungeonNavGrid,
+        cell: (usize, usize),
+        position: Vec3,
+    ) -> Option<Vec3> {
+        let target = self.target?;
+        if self.path.last() != Some(&target) || !self.path.contains(&cell) {
+            let Some(path) = grid.find_path(cell, target) else {
+                self.path.clear();
+                self.target = None;
+                return None;
+            };
+            self.path = path;
+        }
+
+        let index = self.path.iter().position(|&step| step == cell)?;
+        self.path.drain(..index);
+        let next = *self.path.get(1).unwrap_or(&cell);
+        let point = grid.position(next);
+        (next != cell || position.xz().distance(point.xz()) > ARRIVE_DISTANCE).then_some(point)
+    }
+}
+
+/// Направление, в котором враг хочет идти, длиной не больше единицы.
+#[derive(Component, Default)]
+pub struct Steering(pub Vec3);
+
+/// Враг решил ударить свою цель.
+#[derive(Event)]
+pub struct EnemyAttack {
+    pub enemy: Entity,
+    pub target: Entity,
+}
+
+/// Точки обхода комнаты: крайние проходимые клетки в четырех диагональных
+/// направлениях, по порядку обхода вдоль стен.
+pub fn patrol_route(grid: &DungeonNavGrid, cells: &[(i32, i32)]) -> Vec<(usize, usize)> {
+    let cells: Vec<(usize, usize)> = cells
+        .iter()
+        .map(|&(i, j)| (i as usize, j as usize))
+        .filter(|&cell| grid.is_walkable(cell))
+        .collect();
+    let corners: [fn(i64, i64) -> i64; 4] =
+        [|i, j| i + j, |i, j| i - j, |i, j| -i - j, |i, j| j - i];
+
+    let mut route = Vec::new();
+    for corner in corners {
+        let farthest = cells
+            .iter()
+            .max_by_key(|&&(i, j)| corner(i as i64, j as i64));
+        if let Some(&cell) = farthest {
+            if !route.contains(&cell) {
+                route.push(cell);
+            }
+        }
+    }
+    route
+}
+
+/// Переключает состояния врагов по тому, видят ли они игрока и сколько у них здоровья.
+#[allow(clippy::type_complexity)]
+fn think(
+    time: Res<Time>,
+    grid: Res<DungeonNavGrid>,
+    players: Query<(Entity, &Transform), (With<Player>, Without<Dead>)>,
+    mut enemies: Query<
+        (Entity, &Transform, Option<&Health>, &mut EnemyAi),
+        (With<Enemy>, Without<Dead>),
+    >,
+    mut attacks: EventWriter<EnemyAttack>,
+) {
+    let delta = time.delta_seconds();
+    let players: Vec<(Entity, Vec3, (usize, usize))> = players
+        .iter()
+        .filter_map(|(entity, transform)| {
+            let position = transform.translation;
+            Some((entity, position, grid.cell(position)?))
+        })
+        .collect();
+
+    for (entity, transform, health, mut ai) in &mut enemies {
+        let position = transform.translation;
+        let Some(cell) = grid.cell(position) else {
+            continue;
+        };
+        ai.wait = (ai.wait - delta).max(0.0);
+        ai.cooldown = (ai.cooldown - delta).max(0.0);
+
+        let distance = |player_position: Vec3| position.xz().distance(player_position.xz());
+        // В совместной игре враг занят ближайшим игроком
+        let player = players
+            .iter()
+            .copied()
+            .min_by(|a, b| distance(a.1).total_cmp(&distance(b.1)));
+        let near =
+            player.filter(|&(_, player_position, _)| distance(player_position) <= ai.sight_range);
+        let seen = near.filter(|&(_, _, player_cell)| grid.is_visible(cell, player_cell));
+        let hurt = health.is_some_and(|health| health.fraction() <= ai.flee_threshold);
+
+        ai.state = if hurt && (seen.is_some() || ai.state == EnemyState::Flee && near.is_some()) {
+            EnemyState::Flee
+        } else if let Some((target, player_position, player_cell)) = seen {
+            ai.target = Some(player_cell);
+            if distance(player_position) <= ai.attack_range {
+                if ai.cooldown == 0.0 {
+                    attacks.send(EnemyAttack {
+                        enemy: entity,
+                        target,
+                    });
+                    ai.cooldown = ATTACK_COOLDOWN;
+                }
+                EnemyState::Attack
+            } else {
+                EnemyState::Chase
+            }
+        } else {
+            match ai.state {
+                | EnemyState::Idle => {
+                    if ai.wait > 0.0 || ai.patrol.is_empty() {
+                        EnemyState::Idle
+                    } else {
+                        ai.target = Some(ai.patrol[ai.next_patrol % ai.patrol.len()]);
+                        ai.next_patrol += 1;
+                        EnemyState::Patrol
+                    }
+                }
+                | EnemyState::Patrol | EnemyState::Return
+                    if ai.target.is_none() || ai.target == Some(cell) =>
+                {
+                    ai.wait = IDLE_TIME;
+                    EnemyState::Idle
+                }
+                | EnemyState::Patrol => EnemyState::Patrol,
+                | EnemyState::Chase | EnemyState::Attack
+                    if ai.target.is_some_and(|target| target != cell) =>
+                {
+                    EnemyState::Chase
+                }
+                | EnemyState::Chase
+                | EnemyState::Attack
+                | EnemyState::Flee
+                | EnemyState::Return => {
+                    ai.target = grid.cell(ai.home);
+                    EnemyState::Return
+                }
+            }
+        };
+    }
+}
+
+/// Бьет цели врагов их оружием так же, как бьет игрок: стрелки стреляют
+/// в сторону цели, остальные поворачиваются к ней и бьют.
+fn strike(
+    mut attack_event_reader: EventReader<EnemyAttack>,
+    bodies: Query<(&Transform, Has<RangedAttack>)>,
+    mut intents: Query<&mut MovementIntent>,
+) {
+    for attack in attack_event_reader.read() {
+        let (Ok((transform, ranged)), Ok((target, _)), Ok(mut intent)) = (
+            bodies.get(attack.enemy),
+            bodies.get(attack.target),
+            intents.get_mut(attack.enemy),
+        ) else {
+            continue;
+        };
+        let aim = (target.translation - transform.translation) * Vec3::new(1.0, 0.0, 1.0);
+        if ranged {
+            intent.shoot = Some(transform.rotation.inverse() * aim.normalize_or_zero());
+        } else {
+            // Хитбокс открывается перед врагом и поворачивается вместе с ним
+            intent.aim = Some(aim);
+            intent.attack = true;
+        }
+    }
+}
+
+/// Выбирает, куда идти врагу в его текущем состоянии.
+#[allow(clippy::type_complexity)]
+fn steer(
+    grid: Res<DungeonNavGrid>,
+    players: Query<&Transform, (With<Player>, Without<Dead>)>,
+    mut enemies: Query<(&Transform, &mut EnemyAi, &mut Steering), (Without<Player>, Without<Dead>)>,
+) {
+    let players: Vec<(Vec3, (usize, usize))> = players
+        .iter()
+        .filter_map(|transform| {
+            let position = transform.translation;
+            Some((position, grid.cell(position)?))
+        })
+        .collect();
+    let mut flee_map = None;
+
+    for (transform, mut ai, mut steering) in &mut enemies {
+        let position = transform.translation;
+        let Some(cell) = grid.cell(position) else {
+            steering.0 = Vec3::ZERO;
+            continue;
+        };
+
+        let distance = |player: &(Vec3, _)| position.xz().distance(player.0.xz());
+        let player = players
+            .iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .copied();
+        let destination = match ai.state {
+            | EnemyState::Idle | EnemyState::Attack => None,
+            | EnemyState::Flee => player.and_then(|(player, _)| {
+                let map = flee_map.get_or_insert_with(|| {
+                    let cells: Vec<(usize, usize)> =
+                        players.iter().map(|&(_, cell)| cell).collect();
+                    DijkstraMap::new(&grid, &cells)
+                });
+                let distance = map.distance(cell)?;
+                grid.steps(cell)
+                    .filter(|&step| map.distance(step).is_some_and(|next| next > distance))
+                    .map(|step| grid.position(step))
+                    .max_by(|a, b| {
+                        a.xz()
+                            .distance(player.xz())
+                            .total_cmp(&b.xz().distance(player.xz()))
+                    })
+            }),
+            | EnemyState::Chase if player.is_some_and(|(_, player_cell)| player_cell == cell) => {
+                player.map(|(player, _)| player)
+            }
+            | EnemyState::Patrol | EnemyState::Chase | EnemyState::Return => {
+                ai.next_waypoint(&grid, cell, position)
+            }
+        };
+
+        steering.0 = destination
+            .map(|destination| {
+                Vec3::new(destination.x - position.x, 0.0, destination.z - position.z)
+            })
+            .unwrap_or_default()
+            .normalize_or_zero();
+    }
+}
+
+/// Ведет контроллер врага по [`Steering`], как игрока ведет ввод. Намерение
+/// пишется прямо перед шагом контроллера, а не событием за кадр.
+#[allow(clippy::type_complexity)]
+fn apply_steering(
+    mut enemies: Query<
+        (&Transform, &Steering, &mut MovementIntent),
+        (With<EnemyAi>, Without<Dead>),
+    >,
+) {
+    for (transform, steering, mut intent) in &mut enemies {
+        // Контроллер поворачивает направление вместе с телом, а руль задан в мире
+        intent.direction = transform.rotation.inverse() * steering.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::{DUNGEON_COLUMN, DUNGEON_ROW};
+    use crate::testing;
+
+    /// Одна большая комната во весь уровень, `walls` выбиты из нее пустотой.
+    fn arena(walls: &[(usize, usize)]) -> DungeonNavGrid {
+        let cells = (1..DUNGEON_ROW - 1)
+            .flat_map(|i| (1..DUNGEON_COLUMN - 1).map(move |j| (i, j)))
+            .filter(|cell| !walls.contains(cell));
+        testing::arena(cells, [])
+    }
+
+    fn app(grid: DungeonNavGrid) -> App {
+        let mut app = testing::app();
+        app.add_plugins(EnemyAiPlugin).insert_resource(grid);
+        app
+    }
+
+    fn at((i, j): (usize, usize)) -> Vec3 {
+        Vec3::new(i as f32 * 4.0, 0.5, j as f32 * 4.0)
+    }
+
+    fn spawn_enemy(app: &mut App, cell: (usize, usize), patrol: Vec<(usize, usize)>) -> Entity {
+        app.world
+            .spawn((
+                Enemy,
+                EnemyAi::new(at(cell), patrol),
+                Steering::default(),
+                MovementIntent::default(),
+                Health::new(10.0),
+                Transform::from_translation(at(cell)),
+            ))
+            .id()
+    }
+
+    fn state(app: &App, enemy: Entity) -> EnemyState {
+        app.world.get::<EnemyAi>(enemy).unwrap().state
+    }
+
+    fn steering(app: &App, enemy: Entity) -> Vec3 {
+        app.world.get::<Steering>(enemy).unwrap().0
+    }
+
+    fn teleport(app: &mut App, entity: Entity, position: Vec3) {
+        app.world.get_mut::<Transform>(entity).unwrap().translation = position;
+    }
+
+    #[test]
+    fn test_patrol() {
+        let mut app = app(arena(&[]));
+        let enemy = spawn_enemy(&mut app, (3, 3), vec![(3, 8), (3, 3)]);
+
+        app.update();
+        assert_eq!(state(&app, enemy), EnemyState::Patrol);
+        assert!(steering(&app, enemy).z > 0.9);
+
+        teleport(&mut app, enemy, at((3, 8)));
+        app.update();
+        assert_eq!(state(&app, enemy), EnemyState::Idle);
+        assert_eq!(steering(&app, enemy), Vec3::ZERO);
+
+        for _ in 0..25 {
+            app.update();
+        }
+        assert_eq!(state(&app, enemy), EnemyState::Patrol);
+        assert!(steering(&app, enemy).z < -0.9);
+    }
+
+    #[test]
+    fn test_steering_drives_own_controller() {
+        let mut app = app(arena(&[]));
+        let enemy = spawn_enemy(&mut app, (3, 3), vec![(3, 8)]);
+        let idle = spawn_enemy(&mut app, (9, 9), Vec::new());
+        app.world.get_mut::<Transform>(enemy).unwrap().rotation =
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+
+        app.update();
+        let direction =
+            |app: &App, entity| app.world.get::<MovementIntent>(entity).unwrap().direction;
+        let rotation = app.world.get::<Transform>(enemy).unwrap().rotation;
+        assert!((rotation * direction(&app, enemy)).distance(Vec3::Z) < 1e-4);
+        assert_eq!(direction(&app, idle), Vec3::ZERO);
+    }
+
+    #[test]
+    fn test_chase_and_attack() {
+        let mut app = app(arena(&[(4, 6), (5, 6), (6, 6)]));
+        let enemy = spawn_enemy(&mut app, (5, 4), Vec::new());
+        let player = app
+            .world
+            .spawn((Player, Transform::from_translation(at((5, 7)))))
+            .id();
+        let mut attacks = app.world.resource::<Events<EnemyAttack>>().get_reader();
+
+        app.update();
+        assert_eq!(
+            state(&app, enemy),
+            EnemyState::Idle,
+            "sees through the wall"
+        );
+
+        teleport(&mut app, player, at((8, 4)));
+        app.update();
+        assert_eq!(state(&app, enemy), EnemyState::Chase);
+        assert!(steering(&app, enemy).x > 0.9);
+
+        teleport(&mut app, player, at((5, 4)) + Vec3::X);
+        app.update();
+        assert_eq!(state(&app, enemy), EnemyState::Attack);
+        let events = app.world.resource::<Events<EnemyAttack>>();
+        assert_eq!(attacks.read(events).count(), 1);
+        let intent = app.world.get::<MovementIntent>(enemy).unwrap();
+        assert!(
+            intent.aim == Some(Vec3::X) && intent.attack,
+            "turns to the player and swings its weapon"
+        );
+
+        for _ in 0..5 {
+            app.update();
+        }
+        let events = app.world.resource::<Events<EnemyAttack>>();
+        assert_eq!(attacks.read(events).count(), 0, "attacks during cooldown");
+
+        for _ in 0..6 {
+            app.update();
+        }
+        let events = app.world.resource::<Events<EnemyAttack>>();
+        assert_eq!(attacks.read(events).count(), 1);
+    }
+
+    #[test]
+    fn test_flee_and_return() {
+        let mut app = app(arena(&[]));
+        let enemy = spawn_enemy(&mut app, (5, 4), Vec::new());
+        app.world.get_mut::<Health>(enemy).unwrap().current = 2.0;
+        let player = app
+            .world
+            .spawn((Player, Transform::from_translation(at((5, 7)))))
+            .id();
+
+        app.update();
+        assert_eq!(state(&app, enemy), EnemyState::Flee);
+        assert!(steering(&app, enemy).z < -0.9);
+
+        teleport(&mut app, enemy, at((5, 2)));
+        teleport(&mut app, player, at((13, 13)));
+        app.update();
+        assert_eq!(state(&app, enemy), EnemyState::Return);
+        assert!(steering(&app, enemy).z > 0.9);
+
+        teleport(&mut app, enemy, at((5, 4)));
+        app.update();
+        assert_eq!(state(&app, enemy), EnemyState::Idle);
+    }
+
+    #[test]
+    fn test_patrol_route() {
+        let grid = arena(&[]);
+        let cells: Vec<(i32, i32)> = (2..=5).flat_map(|i| (2..=4).map(move |j| (i, j))).collect();
+        assert_eq!(
+            patrol_route(&grid, &cells),
+            vec![(5, 4), (5, 2), (2, 2), (2, 4)]
+        );
+    }
+}
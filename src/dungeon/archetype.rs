@@ -0,0 +1,256 @@
+//! Архетипы врагов, описанные в `assets/data/enemies.ron`: модель, тело,
+//! здоровье, поведение и добыча. Новый враг добавляется правкой файла, без кода.
+
+use crate::dungeon::enums::RoomRole;
+use crate::prelude::*;
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::utils::HashMap;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use serde::Deserialize;
+
+/// Путь к описанию врагов относительно папки `assets`.
+pub const ENEMIES_PATH: &str = "data/enemies.ron";
+
+/// Все архетипы врагов и таблицы их появления.
+/// Пустой каталог означает уровень без врагов.
+#[derive(Resource, Deserialize, Default)]
+pub struct EnemyCatalog {
+    pub archetypes: HashMap<String, EnemyArchetype>,
+    /// Таблицы появления, каждая действует начиная со своей глубины.
+    spawn_tables: Vec<SpawnTable>,
+}
+
+impl EnemyCatalog {
+    /// Читает каталог сразу, а не через `AssetServer`: уровень строится
+    /// на старте и не может ждать фоновой загрузки.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let path = FileAssetReader::get_base_path().join("assets").join(path);
+        let source = std::fs::read_to_string(&path)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        Self::parse(&source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut catalog: Self = ron::from_str(source).map_err(|error| error.to_string())?;
+        catalog.spawn_tables.sort_by_key(|table| table.depth);
+
+        for (id, archetype) in catalog.archetypes.iter() {
+            for entry in archetype.loot.iter() {
+                if !(0.0..=1.0).contains(&entry.chance) {
+                    return Err(format!(
+                        "loot chance {} of `{}` is not between 0 and 1",
+                        entry.chance, id
+                    ));
+                }
+            }
+        }
+        for table in catalog.spawn_tables.iter() {
+            for (id, _) in table.enemies.iter().chain(table.bosses.iter()) {
+                if !catalog.archetypes.contains_key(id) {
+                    return Err(format!(
+                        "unknown archetype `{}` at depth {}",
+                        id, table.depth
+                    ));
+                }
+            }
+        }
+        match catalog.spawn_tables.first() {
+            | Some(table) if table.depth <= 1 => Ok(catalog),
+            | _ => Err("no spawn table for depth 1".to_string()),
+        }
+    }
+
+    /// Таблица с наибольшей глубиной, не превышающей `depth`.
+    /// У пустого каталога таблиц нет.
+    pub fn spawn_table(&self, depth: usize) -> Option<&SpawnTable> {
+        self.spawn_tables
+            .iter()
+            .take_while(|table| table.depth <= depth)
+            .last()
+            .or(self.spawn_tables.first())
+    }
+}
+
+/// Описание одного вида врагов.
+#[derive(Deserialize, Clone)]
+pub struct EnemyArchetype {
+    /// Сцена модели относительно папки `assets`.
+    pub model: String,
+    pub collider: ColliderShape,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// Ускорение при ходьбе.
+    pub speed: f32,
+    pub health: f32,
+    pub damage: f32,
+    /// Сколько очков бюджета стычки стоит этот враг.
+    pub cost: u32,
+    pub ai: AiProfile,
+    #[serde(default)]
+    pub boss: bool,
+    pub on_death: OnDeath,
+    #[serde(default)]
+    pub loot: Vec<LootEntry>,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum ColliderShape {
+    Cylinder { height: f32, radius: f32 },
+    Capsule { height: f32, radius: f32 },
+    Ball { radius: f32 },
+}
+
+impl ColliderShape {
+    pub fn collider(&self) -> Collider {
+        match *self {
+            | ColliderShape::Cylinder { height, radius } => Collider::cylinder(height, radius),
+            | ColliderShape::Capsule { height, radius } => Collider::capsule(height, radius),
+            | ColliderShape::Ball { radius } => Collider::ball(radius),
+        }
+    }
+
+    /// Расстояние от центра коллайдера до его нижней точки.
+    pub fn half_height(&self) -> f32 {
+        match *self {
+            | ColliderShape::Cylinder { height, .. } => height / 2.0,
+            | ColliderShape::Capsule { height, radius } => height / 2.0 + radius,
+            | ColliderShape::Ball { radius } => radius,
+        }
+    }
+}
+
+/// Как далеко враг видит и бьет и когда убегает.
+#[derive(Deserialize, Clone)]
+pub struct AiProfile {
+    pub sight_range: f32,
+    pub attack_range: f32,
+    pub flee_threshold: f32,
+    /// Стрелок вместо удара выпускает снаряд.
+    #[serde(default)]
+    pub ranged: Option<RangedProfile>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct RangedProfile {
+    pub damage: f32,
+    pub speed: f32,
+    /// Снаряд летит по дуге, как брошенный предмет.
+    #[serde(default)]
+    pub thrown: bool,
+}
+
+/// Что может остаться после врага и с каким шансом.
+#[derive(Deserialize, Clone, Copy)]
+pub struct LootEntry {
+    pub feature: RoomRole,
+    pub chance: f64,
+}
+
+/// Веса архетипов обычных врагов и боссов на одной глубине.
+#[derive(Deserialize)]
+pub struct SpawnTable {
+    pub depth: usize,
+    pub enemies: Vec<(String, u32)>,
+    pub bosses: Vec<(String, u32)>,
+}
+
+impl SpawnTable {
+    pub fn pick_boss(&self, rng: &mut impl Rng) -> Option<&str> {
+        pick(self.bosses.iter(), rng)
+    }
+}
+
+/// Случайный архетип с учетом весов. `None`, если выбирать не из чего.
+pub fn pick<'a>(
+    entries: impl Iterator<Item = &'a (String, u32)> + Clone,
+    rng: &mut impl Rng,
+) -> Option<&'a str> {
+    let weights = WeightedIndex::new(entries.clone().map(|(_, weight)| *weight)).ok()?;
+    entries.map(|(id, _)| id.as_str()).nth(weights.sample(rng))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_shipped_catalog() {
+        let catalog = EnemyCatalog::load(ENEMIES_PATH).unwrap();
+        assert!(catalog.archetypes.values().any(|archetype| archetype.boss));
+        assert_eq!(catalog.spawn_table(0).unwrap().depth, 1);
+        assert_eq!(catalog.spawn_table(2).unwrap().depth, 1);
+        assert_eq!(catalog.spawn_table(100).unwrap().depth, 3);
+    }
+
+    #[test]
+    fn test_weighted_pick() {
+        let catalog = EnemyCatalog::parse(
+            r#"(
+                archetypes: {
+                    "a": (
+                        model: "a.glb#Scene0",
+                        collider: Ball(radius: 0.5),
+                        speed: 1.0,
+                        health: 1.0,
+                        damage: 1.0,
+                        cost: 1,
+                        ai: (sight_range: 1.0, attack_range: 1.0, flee_threshold: 0.0),
+                        on_death: Despawn,
+                    ),
+                    "b": (
+                        model: "b.glb#Scene0",
+                        collider: Ball(radius: 0.5),
+                        speed: 1.0,
+                        health: 1.0,
+                        damage: 1.0,
+                        cost: 1,
+                        ai: (sight_range: 1.0, attack_range: 1.0, flee_threshold: 0.0),
+                        on_death: Despawn,
+                    ),
+                },
+                spawn_tables: [(depth: 1, enemies: [("a", 3), ("b", 1)], bosses: [("b", 1), ("a", 0)])],
+            )"#,
+        )
+        .unwrap();
+        let table = catalog.spawn_table(1).unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+        let picks: Vec<&str> = (0..400)
+            .map(|_| pick(table.enemies.iter(), &mut rng).unwrap())
+            .collect();
+        let a = picks.iter().filter(|id| **id == "a").count();
+        assert!((250..350).contains(&a), "picked `a` {} times of 400", a);
+        assert!((0..50).all(|_| table.pick_boss(&mut rng) == Some("b")));
+        assert_eq!(pick(table.enemies.iter().take(0), &mut rng), None);
+
+        let unknown =
+            r#"(archetypes: {}, spawn_tables: [(depth: 1, enemies: [("c", 1)], bosses: [])])"#;
+        assert!(EnemyCatalog::parse(unknown).is_err());
+
+        let chance = r#"(
+            archetypes: {
+                "a": (
+                    model: "a.glb#Scene0",
+                    collider: Ball(radius: 0.5),
+                    speed: 1.0,
+                    health: 1.0,
+                    damage: 1.0,
+                    cost: 1,
+                    ai: (sight_range: 1.0, attack_range: 1.0, flee_threshold: 0.0),
+                    on_death: Despawn,
+                    loot: [(feature: Treasure, chance: 1.5)],
+                ),
+            },
+            spawn_tables: [(depth: 1, enemies: [("a", 1)], bosses: [])],
+        )"#;
+        assert!(EnemyCatalog::parse(chance)
+            .err()
+            .is_some_and(|error| error.starts_with("loot chance")));
+    }
+}
@@ -2,10 +2,14 @@ mod spawn_door;
 mod spawn_enemy;
 mod spawn_floor;
 mod spawn_player;
+mod spawn_prop;
+mod spawn_room_feature;
 mod spawn_wall;
 
 pub use spawn_door::SpawnDoor;
 pub use spawn_enemy::SpawnEnemy;
 pub use spawn_floor::SpawnFloor;
 pub use spawn_player::SpawnPlayer;
+pub use spawn_prop::SpawnProp;
+pub use spawn_room_feature::SpawnRoomFeature;
 pub use spawn_wall::SpawnWall;
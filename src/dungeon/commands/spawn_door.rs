@@ -1,6 +1,7 @@
 use bevy::ecs::system::Command;
 use bevy::prelude::*;
 
+use crate::dungeon::components::{Tile, Wall};
 use crate::dungeon::enums::DoorType;
 
 pub struct SpawnDoor {
@@ -59,7 +60,11 @@ impl Command for SpawnDoor {
                 }
             }
 
-            world.spawn_batch(batch);
+            world.spawn_batch(
+                batch
+                    .into_iter()
+                    .map(|scene| (scene, Tile(self.position), Wall)),
+            );
         }
     }
 }
@@ -1,36 +1,113 @@
 use crate::prelude::*;
 
-use crate::dungeon::components::Enemy;
+use crate::dungeon::ai::{EnemyAi, Steering};
+use crate::dungeon::archetype::EnemyCatalog;
+use crate::dungeon::components::{Boss, Enemy, LootDrop};
+use bevy_xpbd_3d::math::{Scalar, Vector};
+
+/// Сколько секунд враг неуязвим после удара.
+const INVULNERABILITY_TIME: f32 = 0.2;
+/// Самая высокая ступенька, на которую враг заходит без прыжка.
+const STEP_HEIGHT: Scalar = 0.3;
+/// С какой высоты враг спускается, не отрываясь от пола.
+const SNAP_DISTANCE: Scalar = 0.3;
+/// Обрыв глубже этого враг считает краем и к нему не подходит.
+const MAX_DROP: Scalar = 1.0;
 
 pub struct SpawnEnemy {
+    /// Имя архетипа из [`EnemyCatalog`].
+    pub archetype: String,
     pub position: Vec3,
+    pub patrol: Vec<(usize, usize)>,
 }
 
 impl SpawnEnemy {
-    pub fn new(x: f32, y: f32, z: f32) -> Self {
+    pub fn new(archetype: impl Into<String>, x: f32, y: f32, z: f32) -> Self {
         Self {
+            archetype: archetype.into(),
             position: Vec3 { x, y, z },
+            patrol: Vec::new(),
         }
     }
+
+    pub fn patrol(mut self, patrol: Vec<(usize, usize)>) -> Self {
+        self.patrol = patrol;
+        self
+    }
 }
 
 impl Command for SpawnEnemy {
     fn apply(self, world: &mut World) {
+        let Some(archetype) = world
+            .get_resource::<EnemyCatalog>()
+            .and_then(|catalog| catalog.archetypes.get(&self.archetype))
+            .cloned()
+        else {
+            warn!("unknown enemy archetype `{}`", self.archetype);
+            return;
+        };
+
         if let Some(asset_server) = world.get_resource::<AssetServer>() {
-            world.spawn((
+            let scale = archetype.scale;
+            let mut ai = EnemyAi::new(self.position, self.patrol)
+                .with_flee_threshold(archetype.ai.flee_threshold);
+            ai.sight_range = archetype.ai.sight_range;
+            ai.attack_range = archetype.ai.attack_range;
+
+            let mut enemy = world.spawn((
                 Enemy,
-                RigidBody::Dynamic,
-                Collider::cylinder(0.5, 0.4),
+                ai,
+                Steering::default(),
+                Health::new(archetype.health),
+                Invulnerability::new(INVULNERABILITY_TIME),
+                archetype.on_death,
+                LootDrop(archetype.loot),
+                CharacterControllerBundle::new(
+                    archetype.collider.collider(),
+                    Vector::NEG_Y * 9.81 * 2.0,
+                )
+                .with_movement(
+                    archetype.speed,
+                    0.1,
+                    30.0,
+                    0.1,
+                    7.0,
+                    (30.0 as Scalar).to_radians(),
+                ),
+                StepUp(STEP_HEIGHT),
+                GroundSnap(SNAP_DISTANCE),
+                LedgeDetection::new(MAX_DROP).stopping(),
                 SceneBundle {
-                    scene: asset_server.load("models/barrel_large.glb#Scene0"),
+                    scene: asset_server.load(archetype.model),
                     transform: Transform::from_xyz(
                         self.position.x,
-                        self.position.y,
+                        self.position.y + archetype.collider.half_height() * scale,
                         self.position.z,
-                    ),
+                    )
+                    .with_scale(Vec3::splat(scale)),
                     ..default()
                 },
             ));
+            if archetype.boss {
+                enemy.insert(Boss);
+            }
+            match archetype.ai.ranged {
+                | Some(ranged) => {
+                    let weapon = RangedAttack::new(ranged.damage, ranged.speed);
+                    enemy.insert(if ranged.thrown {
+                        weapon.thrown()
+                    } else {
+                        weapon
+                    });
+                }
+                | None => {
+                    // Хитбокс растет вместе с врагом, а достать должен на дальность атаки
+                    let reach = archetype.ai.attack_range / scale;
+                    let mut weapon = MeleeAttack::new(archetype.damage, reach / 2.0);
+                    weapon.size.z = reach;
+                    enemy.insert(weapon);
+                }
+            }
         }
     }
 }
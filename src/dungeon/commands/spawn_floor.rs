@@ -1,3 +1,4 @@
+use super::super::components::{Pit, Tile};
 use super::super::enums::FloorType;
 use crate::prelude::*;
 
@@ -17,7 +18,7 @@ impl SpawnFloor {
 
 fn floor_model(floor_type: FloorType) -> Option<String> {
     match floor_type {
-        | FloorType::Empthy => None,
+        | FloorType::Empthy | FloorType::Pit => None,
         | FloorType::Room => Some("models/floor/floor_tile_large.glb#Scene0".to_string()),
         | FloorType::Path => Some("models/floor/floor_tile_large.glb#Scene0".to_string()),
     }
@@ -28,15 +29,52 @@ impl Command for SpawnFloor {
         if let Some(asset_server) = world.get_resource::<AssetServer>() {
             match self.floor_type {
                 | floor_type @ FloorType::Room | floor_type @ FloorType::Path => {
-                    world.spawn((SceneBundle {
-                        scene: asset_server.load(floor_model(floor_type).unwrap()),
-                        transform: Transform::from_xyz(
+                    world.spawn((
+                        SceneBundle {
+                            scene: asset_server.load(floor_model(floor_type).unwrap()),
+                            transform: Transform::from_xyz(
+                                self.position.x,
+                                self.position.y,
+                                self.position.z,
+                            ),
+                            ..default()
+                        },
+                        Tile(self.position),
+                    ));
+                }
+                | FloorType::Pit => {
+                    let mesh_handle =
+                        world.resource_scope(|_world, mut meshes: Mut<Assets<Mesh>>| {
+                            meshes.add(Mesh::from(shape::Box::new(4.0, 0.1, 4.0)))
+                        });
+
+                    let material_handle = world.resource_scope(
+                        |_world, mut materials: Mut<Assets<StandardMaterial>>| {
+                            materials.add(Color::rgb(0., 0., 0.).into())
+                        },
+                    );
+
+                    world.spawn((
+                        PbrBundle {
+                            mesh: mesh_handle,
+                            material: material_handle,
+                            transform: Transform::from_xyz(self.position.x, 0.0, self.position.z),
+                            ..default()
+                        },
+                        Tile(self.position),
+                    ));
+                    // Край ямы прощает шаг мимо: падает только тот, кто зашел в нее
+                    world.spawn((
+                        Pit,
+                        RigidBody::Static,
+                        Sensor,
+                        Collider::cuboid(3.0, 1.0, 3.0),
+                        TransformBundle::from_transform(Transform::from_xyz(
                             self.position.x,
-                            self.position.y,
+                            0.5,
                             self.position.z,
-                        ),
-                        ..default()
-                    },));
+                        )),
+                    ));
                 }
                 | FloorType::Empthy => {
                     let mesh_handle =
@@ -50,12 +88,15 @@ impl Command for SpawnFloor {
                         },
                     );
 
-                    world.spawn((PbrBundle {
-                        mesh: mesh_handle,
-                        material: material_handle,
-                        transform: Transform::from_xyz(self.position.x, 2.0, self.position.z),
-                        ..default()
-                    },));
+                    world.spawn((
+                        PbrBundle {
+                            mesh: mesh_handle,
+                            material: material_handle,
+                            transform: Transform::from_xyz(self.position.x, 2.0, self.position.z),
+                            ..default()
+                        },
+                        Tile(self.position),
+                    ));
                 }
             }
         }
@@ -1,69 +1,107 @@
 use crate::prelude::*;
 
-use crate::dungeon::components::{Player, PlayerCamera};
+use crate::dungeon::components::Player;
 use bevy_xpbd_3d::math::{Scalar, Vector};
 
+const PLAYER_HEALTH: f32 = 100.0;
+/// Сколько секунд игрок неуязвим после удара.
+const INVULNERABILITY_TIME: f32 = 1.0;
+const MELEE_DAMAGE: f32 = 10.0;
+const MELEE_REACH: f32 = 0.8;
+const THROW_DAMAGE: f32 = 6.0;
+const THROW_SPEED: f32 = 12.0;
+/// Самая высокая ступенька, на которую игрок заходит без прыжка.
+const STEP_HEIGHT: Scalar = 0.3;
+/// С какой высоты игрок спускается, не отрываясь от пола.
+const SNAP_DISTANCE: Scalar = 0.3;
+const STAMINA: f32 = 100.0;
+/// Сколько выносливости восстанавливается за секунду.
+const STAMINA_REGENERATION: f32 = 30.0;
+/// Во сколько раз быстрее игрок разгоняется в спринте и сколько
+/// выносливости тратит за секунду.
+const SPRINT_MULTIPLIER: Scalar = 1.6;
+const SPRINT_STAMINA: f32 = 20.0;
+/// Рывок: скорость, длительность, перезарядка и время неуязвимости.
+const DASH_SPEED: Scalar = 20.0;
+const DASH_TIME: f32 = 0.15;
+const DASH_COOLDOWN: f32 = 0.8;
+/// Перекат: скорость, длительность, перезарядка, время неуязвимости и цена в выносливости.
+const ROLL_SPEED: Scalar = 10.0;
+const ROLL_TIME: f32 = 0.4;
+const ROLL_COOLDOWN: f32 = 0.6;
+const ROLL_INVULNERABILITY: f32 = 0.3;
+const ROLL_STAMINA: f32 = 25.0;
+
 pub struct SpawnPlayer {
     pub position: Vec3,
+    pub id: usize,
 }
 
 impl SpawnPlayer {
     pub fn new(x: f32, y: f32, z: f32) -> Self {
         Self {
             position: Vec3 { x, y, z },
+            id: 0,
         }
     }
+
+    /// Номер игрока в совместной игре, первый игрок нулевой.
+    pub fn player(mut self, id: usize) -> Self {
+        self.id = id;
+        self
+    }
 }
 
 impl Command for SpawnPlayer {
     fn apply(self, world: &mut World) {
         if let Some(asset_server) = world.get_resource::<AssetServer>() {
-            world
-                .spawn((
-                    Player,
-                    CharacterControllerBundle::new(
-                        Collider::capsule(0.25, 0.5),
-                        Vector::NEG_Y * 9.81 * 2.0,
-                    )
-                    .with_movement(
-                        500.0,
-                        0.1,
-                        30.0,
-                        0.1,
-                        7.0,
-                        (30.0 as Scalar).to_radians(),
+            world.spawn((
+                Player,
+                PlayerControlled,
+                PlayerId(self.id),
+                CameraTarget,
+                Health::new(PLAYER_HEALTH),
+                Invulnerability::new(INVULNERABILITY_TIME),
+                OnDeath::Corpse,
+                MeleeAttack::new(MELEE_DAMAGE, MELEE_REACH),
+                RangedAttack::new(THROW_DAMAGE, THROW_SPEED).thrown(),
+                CharacterControllerBundle::new(
+                    Collider::capsule(0.25, 0.5),
+                    Vector::NEG_Y * 9.81 * 2.0,
+                )
+                .with_movement(
+                    500.0,
+                    0.1,
+                    30.0,
+                    0.1,
+                    7.0,
+                    (30.0 as Scalar).to_radians(),
+                ),
+                StepUp(STEP_HEIGHT),
+                GroundSnap(SNAP_DISTANCE),
+                (
+                    Stamina::new(STAMINA, STAMINA_REGENERATION),
+                    Sprint::new(SPRINT_MULTIPLIER, SPRINT_STAMINA),
+                    Dash(
+                        Burst::new(DASH_SPEED, DASH_TIME, DASH_COOLDOWN)
+                            .with_invulnerability(DASH_TIME),
+                    ),
+                    DodgeRoll(
+                        Burst::new(ROLL_SPEED, ROLL_TIME, ROLL_COOLDOWN)
+                            .with_invulnerability(ROLL_INVULNERABILITY)
+                            .with_stamina(ROLL_STAMINA),
+                    ),
+                ),
+                SceneBundle {
+                    scene: asset_server.load("models/characters/barbarian.glb#Scene0"),
+                    transform: Transform::from_xyz(
+                        self.position.x,
+                        self.position.y,
+                        self.position.z,
                     ),
-                    SceneBundle {
-                        scene: asset_server.load("models/characters/barbarian.glb#Scene0"),
-                        transform: Transform::from_xyz(
-                            self.position.x,
-                            self.position.y,
-                            self.position.z,
-                        ),
-                        ..default()
-                    },
-                ))
-                .with_children(|parent| {
-                    parent.spawn((
-                        PlayerCamera,
-                        LookTransformBundle {
-                            transform: LookTransform::new(
-                                Vec3::new(2.0, 5.0, 5.0),
-                                Vec3::ZERO,
-                                Vec3::Y,
-                            ),
-                            smoother: Smoother::new(0.9), // Value between 0.0 and 1.0, higher is smoother.
-                        },
-                        Camera3dBundle {
-                            projection: PerspectiveProjection {
-                                fov: 75.0_f32.to_radians(),
-                                ..default()
-                            }
-                            .into(),
-                            ..default()
-                        },
-                    ));
-                });
+                    ..default()
+                },
+            ));
         }
     }
 }
@@ -0,0 +1,138 @@
+use crate::dungeon::components::Tile;
+use crate::dungeon::enums::{CornerType, PropType, WallType};
+use crate::prelude::*;
+
+/// Насколько проп сдвинут от центра тайла к своей стене.
+const WALL_OFFSET: f32 = 1.3;
+/// Сломанная стена стоит вплотную к целой.
+const BROKEN_WALL_OFFSET: f32 = 1.6;
+
+pub struct SpawnProp {
+    pub position: Vec3,
+    pub prop_type: PropType,
+    pub anchor: WallType,
+}
+
+impl SpawnProp {
+    pub fn new(x: f32, y: f32, z: f32, prop_type: PropType, anchor: WallType) -> Self {
+        Self {
+            position: Vec3 { x, y, z },
+            prop_type,
+            anchor,
+        }
+    }
+}
+
+fn anchor_offset(anchor: WallType, offset: f32) -> Vec3 {
+    match anchor {
+        | WallType::Bottom => Vec3::new(-offset, 0.0, 0.0),
+        | WallType::Top => Vec3::new(offset, 0.0, 0.0),
+        | WallType::Left => Vec3::new(0.0, 0.0, -offset),
+        | WallType::Right => Vec3::new(0.0, 0.0, offset),
+        | WallType::InternalCorner(CornerType::BottomLeft) => Vec3::new(-offset, 0.0, -offset),
+        | WallType::InternalCorner(CornerType::BottomRight) => Vec3::new(-offset, 0.0, offset),
+        | WallType::InternalCorner(CornerType::TopLeft) => Vec3::new(offset, 0.0, -offset),
+        | WallType::InternalCorner(CornerType::TopRight) => Vec3::new(offset, 0.0, offset),
+    }
+}
+
+fn anchor_rotation(anchor: WallType) -> Quat {
+    let angle: f32 = match anchor {
+        | WallType::Bottom => 90.0,
+        | WallType::Right => 180.0,
+        | WallType::Top => 270.0,
+        | WallType::Left | WallType::InternalCorner(_) => 0.0,
+    };
+    Quat::from_rotation_y(angle.to_radians())
+}
+
+fn cube_bundle(world: &mut World, color: Color, transform: Transform) -> PbrBundle {
+    let mesh = world.resource_scope(|_world, mut meshes: Mut<Assets<Mesh>>| {
+        meshes.add(Mesh::from(shape::Cube { size: 1.0 }))
+    });
+    let material = world.resource_scope(|_world, mut materials: Mut<Assets<StandardMaterial>>| {
+        materials.add(color.into())
+    });
+    PbrBundle {
+        mesh,
+        material,
+        transform,
+        ..default()
+    }
+}
+
+impl Command for SpawnProp {
+    fn apply(self, world: &mut World) {
+        if let Some(asset_server) = world.get_resource::<AssetServer>() {
+            let barrel_scene = asset_server.load("models/barrel_large.glb#Scene0");
+            let broken_wall_scene = asset_server.load("models/wall/wall_broken.glb#Scene0");
+
+            match self.prop_type {
+                | PropType::Empthy => {}
+                | PropType::Barrel => {
+                    let position = self.position + anchor_offset(self.anchor, WALL_OFFSET);
+                    world
+                        .spawn((
+                            Tile(self.position),
+                            RigidBody::Static,
+                            SceneBundle {
+                                scene: barrel_scene,
+                                transform: Transform::from_translation(position),
+                                ..default()
+                            },
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                Collider::cylinder(1.0, 0.45),
+                                TransformBundle::from_transform(Transform::from_xyz(0.0, 0.5, 0.0)),
+                            ));
+                        });
+                }
+                | PropType::Crate => {
+                    let position = self.position
+                        + anchor_offset(self.anchor, WALL_OFFSET)
+                        + Vec3::new(0.0, 0.5, 0.0);
+                    let transform = Transform::from_translation(position)
+                        .with_rotation(anchor_rotation(self.anchor));
+                    let bundle = cube_bundle(world, Color::rgb(0.45, 0.3, 0.15), transform);
+                    world.spawn((
+                        bundle,
+                        Tile(self.position),
+                        RigidBody::Static,
+                        Collider::cuboid(1.0, 1.0, 1.0),
+                    ));
+                }
+                | PropType::BrokenWall => {
+                    let position = self.position + anchor_offset(self.anchor, BROKEN_WALL_OFFSET);
+                    world
+                        .spawn((
+                            Tile(self.position),
+                            RigidBody::Static,
+                            SceneBundle {
+                                scene: broken_wall_scene,
+                                transform: Transform::from_translation(position)
+                                    .with_rotation(anchor_rotation(self.anchor)),
+                                ..default()
+                            },
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                Collider::cuboid(4.0, 4.0, 0.5),
+                                TransformBundle::from_transform(Transform::from_xyz(0.0, 2.0, 0.0)),
+                            ));
+                        });
+                }
+                | PropType::Debris => {
+                    let position = self.position
+                        + anchor_offset(self.anchor, WALL_OFFSET)
+                        + Vec3::new(0.0, 0.05, 0.0);
+                    let transform = Transform::from_translation(position)
+                        .with_rotation(Quat::from_rotation_y(25.0_f32.to_radians()))
+                        .with_scale(Vec3::new(0.8, 0.1, 0.6));
+                    let bundle = cube_bundle(world, Color::rgb(0.35, 0.33, 0.3), transform);
+                    world.spawn((bundle, Tile(self.position)));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::prelude::*;
+
+use crate::dungeon::components::{RoomFeature, Tile};
+use crate::dungeon::enums::RoomRole;
+
+pub struct SpawnRoomFeature {
+    pub position: Vec3,
+    pub role: RoomRole,
+}
+
+impl SpawnRoomFeature {
+    pub fn new(x: f32, y: f32, z: f32, role: RoomRole) -> Self {
+        Self {
+            position: Vec3 { x, y, z },
+            role,
+        }
+    }
+}
+
+/// Размер и цвет заглушки, которой обозначено содержимое комнаты.
+fn feature_look(role: RoomRole) -> Option<(Vec3, Color)> {
+    match role {
+        | RoomRole::Treasure => Some((Vec3::new(1.2, 0.8, 0.8), Color::GOLD)),
+        | RoomRole::Shop => Some((Vec3::new(2.5, 1.0, 1.0), Color::DARK_GREEN)),
+        | RoomRole::Shrine => Some((Vec3::new(0.8, 2.5, 0.8), Color::ALICE_BLUE)),
+        | RoomRole::Common | RoomRole::Start | RoomRole::Boss => None,
+    }
+}
+
+impl Command for SpawnRoomFeature {
+    fn apply(self, world: &mut World) {
+        let Some((size, color)) = feature_look(self.role) else {
+            return;
+        };
+
+        let mesh_handle = world.resource_scope(|_world, mut meshes: Mut<Assets<Mesh>>| {
+            meshes.add(Mesh::from(shape::Box::new(size.x, size.y, size.z)))
+        });
+        let material_handle =
+            world.resource_scope(|_world, mut materials: Mut<Assets<StandardMaterial>>| {
+                materials.add(StandardMaterial {
+                    base_color: color,
+                    emissive: color * 0.3,
+                    ..default()
+                })
+            });
+
+        world.spawn((
+            RoomFeature(self.role),
+            Tile(self.position),
+            RigidBody::Static,
+            Collider::cuboid(size.x, size.y, size.z),
+            PbrBundle {
+                mesh: mesh_handle,
+                material: material_handle,
+                transform: Transform::from_xyz(
+                    self.position.x,
+                    self.position.y + size.y / 2.0,
+                    self.position.z,
+                ),
+                ..default()
+            },
+        ));
+    }
+}
@@ -1,6 +1,11 @@
+use crate::dungeon::components::{Tile, Wall};
 use crate::dungeon::enums::{CornerType, WallType};
 use bevy::ecs::system::Command;
 use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+
+/// Размеры стены вдоль клетки, в высоту и в толщину.
+const WALL_SIZE: Vec3 = Vec3::new(4.0, 8.0, 0.5);
 
 pub struct SpawnWall {
     pub position: Vec3,
@@ -99,7 +104,15 @@ impl Command for SpawnWall {
                 }
             }
 
-            world.spawn_batch(batch);
+            world.spawn_batch(batch.into_iter().map(|scene| {
+                (
+                    scene,
+                    Tile(self.position),
+                    Wall,
+                    RigidBody::Static,
+                    Collider::cuboid(WALL_SIZE.x, WALL_SIZE.y, WALL_SIZE.z),
+                )
+            }));
         }
     }
 }
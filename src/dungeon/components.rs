@@ -1,10 +1,37 @@
 use bevy::ecs::component::Component;
+use bevy::math::Vec3;
 
-#[derive(Component)]
-pub struct PlayerCamera;
+use crate::dungeon::archetype::LootEntry;
+use crate::dungeon::enums::RoomRole;
 
 #[derive(Component)]
 pub struct Player;
 
 #[derive(Component)]
 pub struct Enemy;
+
+#[derive(Component)]
+pub struct Boss;
+
+/// Часть уровня, которую прячет туман войны. Хранит центр своей клетки:
+/// стены и пропы стоят со смещением к ее краю.
+#[derive(Component)]
+pub struct Tile(pub Vec3);
+
+/// Стена или дверной проем вдоль края клетки. Стены между камерой
+/// и игроком вырезаются, как в кукольном домике.
+#[derive(Component)]
+pub struct Wall;
+
+/// Яма в полу комнаты. Кто в нее ступил, падает и погибает.
+#[derive(Component)]
+pub struct Pit;
+
+/// Содержимое особой комнаты: сундук, прилавок или алтарь.
+#[derive(Component)]
+pub struct RoomFeature(pub RoomRole);
+
+/// Враг, после смерти которого может остаться добыча: каждая запись
+/// выпадает независимо со своим шансом.
+#[derive(Component)]
+pub struct LootDrop(pub Vec<LootEntry>);
@@ -0,0 +1,171 @@
+//! Разрез стен: стены между камерой и игроком прячутся, как в кукольном
+//! домике, чтобы игрока всегда было видно.
+
+use crate::prelude::*;
+
+use crate::dungeon::components::{Tile, Wall};
+use crate::dungeon::fog::{hide_tiles, FogOfWar, FogState};
+use crate::dungeon::DungeonNavGrid;
+use bevy::utils::{HashMap, HashSet};
+
+/// Половина длины стены вдоль края клетки.
+const WALL_HALF_LENGTH: f32 = 2.0;
+/// Шаг, с которым проверяются клетки на отрезке от игрока до камеры.
+const SAMPLE_STEP: f32 = 0.5;
+
+pub struct CutawayPlugin;
+
+impl Plugin for CutawayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WallIndex>().add_systems(
+            Update,
+            (index_walls, cut_away_walls)
+                .chain()
+                .after(hide_tiles)
+                .run_if(resource_exists::<DungeonNavGrid>()),
+        );
+    }
+}
+
+/// Стены, сгруппированные по клеткам уровня.
+#[derive(Resource, Default)]
+struct WallIndex(HashMap<(usize, usize), Vec<Entity>>);
+
+/// Стена спрятана, потому что загораживает игрока.
+#[derive(Component)]
+struct CutAway;
+
+fn index_walls(
+    grid: Res<DungeonNavGrid>,
+    mut index: ResMut<WallIndex>,
+    walls: Query<(Entity, &Tile), Added<Wall>>,
+) {
+    for (entity, tile) in &walls {
+        if let Some(cell) = grid.cell(tile.0) {
+            index.0.entry(cell).or_default().push(entity);
+        }
+    }
+}
+
+/// Прячет стены, пересекающие отрезки от игроков до камеры,
+/// и возвращает остальным видимость по туману войны.
+#[allow(clippy::type_complexity)]
+fn cut_away_walls(
+    mut commands: Commands,
+    grid: Res<DungeonNavGrid>,
+    fog: Res<FogOfWar>,
+    index: Res<WallIndex>,
+    targets: Query<&Transform, With<CameraTarget>>,
+    cameras: Query<&Transform, With<GameCamera>>,
+    mut walls: Query<(Entity, &Tile, &Transform, &mut Visibility, Has<CutAway>), With<Wall>>,
+) {
+    let mut obstructing = HashSet::new();
+    let camera = cameras.iter().next().map(|camera| camera.translation.xz());
+    // В совместной игре стены вырезаются перед каждым игроком
+    for (target, to) in targets.iter().filter_map(|target| Some((target, camera?))) {
+        let from = target.translation.xz();
+        let samples = (from.distance(to) / SAMPLE_STEP).ceil() as usize;
+        let cells: HashSet<(usize, usize)> = (0..=samples)
+            .filter_map(|k| {
+                let point = from.lerp(to, k as f32 / samples.max(1) as f32);
+                grid.cell(Vec3::new(point.x, 0.0, point.y))
+            })
+            .collect();
+        for cell in cells {
+            for &entity in index.0.get(&cell).into_iter().flatten() {
+                let Ok((_, _, transform, _, _)) = walls.get(entity) else {
+                    continue;
+                };
+                let along = (transform.rotation * Vec3::X).xz() * WALL_HALF_LENGTH;
+                let center = transform.translation.xz();
+                if segments_intersect(from, to, center - along, center + along) {
+                    obstructing.insert(entity);
+                }
+            }
+        }
+    }
+
+    for (entity, tile, _, mut visibility, cut) in &mut walls {
+        if obstructing.contains(&entity) {
+            // Туман мог вернуть стене видимость, поэтому прячем ее каждый кадр
+            if *visibility != Visibility::Hidden {
+                *visibility = Visibility::Hidden;
+            }
+            if !cut {
+                commands.entity(entity).insert(CutAway);
+            }
+        } else if cut {
+            let state = grid
+                .cell(tile.0)
+                .map_or(FogState::Unseen, |cell| fog.state(cell));
+            *visibility = match state {
+                | FogState::Unseen => Visibility::Hidden,
+                | FogState::Seen | FogState::Visible => Visibility::Inherited,
+            };
+            commands.entity(entity).remove::<CutAway>();
+        }
+    }
+}
+
+/// Пересекаются ли отрезки `a1a2` и `b1b2` на плоскости.
+fn segments_intersect(a1: Vec2, a2: Vec2, b1: Vec2, b2: Vec2) -> bool {
+    let side = |p: Vec2, q: Vec2, r: Vec2| (q - p).perp_dot(r - p);
+    let (d1, d2) = (side(b1, b2, a1), side(b1, b2, a2));
+    let (d3, d4) = (side(a1, a2, b1), side(a1, a2, b2));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn grid() -> DungeonNavGrid {
+        let room = (1..4).flat_map(|i| (1..4).map(move |j| (i, j)));
+        testing::arena(room, [])
+    }
+
+    #[test]
+    fn test_cut_away_walls() {
+        let mut app = testing::app();
+        app.add_plugins(CutawayPlugin)
+            .insert_resource(grid())
+            .insert_resource(FogOfWar::from_explored(&[(2, 2), (2, 3)]));
+        app.world
+            .spawn((CameraTarget, Transform::from_xyz(8.0, 0.0, 8.0)));
+        let camera = app
+            .world
+            .spawn((
+                GameCamera {
+                    mode: CameraMode::ThirdPerson,
+                    orbit: Vec2::ZERO,
+                },
+                Transform::from_xyz(8.0, 4.0, 14.0),
+            ))
+            .id();
+        // Стена по краю клетки между игроком и камерой
+        let wall = |app: &mut App, z: f32| {
+            app.world
+                .spawn((
+                    Wall,
+                    Tile(Vec3::new(8.0, 0.0, 8.0)),
+                    Transform::from_xyz(8.0, 0.0, z),
+                    Visibility::Inherited,
+                ))
+                .id()
+        };
+        let between = wall(&mut app, 10.0);
+        let behind = wall(&mut app, 6.0);
+        app.update();
+
+        let visibility = |app: &App, entity| *app.world.get::<Visibility>(entity).unwrap();
+        assert_eq!(visibility(&app, between), Visibility::Hidden);
+        assert_eq!(visibility(&app, behind), Visibility::Inherited);
+
+        app.world.get_mut::<Transform>(camera).unwrap().translation = Vec3::new(8.0, 4.0, 2.0);
+        app.update();
+
+        assert_eq!(visibility(&app, between), Visibility::Inherited);
+        assert_eq!(visibility(&app, behind), Visibility::Hidden);
+    }
+}
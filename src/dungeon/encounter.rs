@@ -0,0 +1,204 @@
+//! Режиссер стычек: раздает комнатам бюджет сложности по глубине и роли
+//! комнаты и набирает под него врагов из таблицы появления.
+
+use crate::dungeon::archetype::{pick, EnemyCatalog};
+use crate::dungeon::enums::{MarkerType, RoomRole};
+use crate::dungeon::level::layer::room::Room;
+use crate::dungeon::{DungeonNavGrid, DUNGEON_COLUMN, DUNGEON_ROW};
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+/// Ближе этого числа клеток (по сторонам) к старту игрока враги не появляются.
+const START_CLEARANCE: usize = 4;
+/// Враги не появляются в дверях и на соседних с ними клетках,
+/// чтобы не запирать проходы и не бить игрока с порога.
+const DOOR_CLEARANCE: usize = 1;
+
+/// Один враг, которого нужно поставить на уровень.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Encounter {
+    pub archetype: String,
+    pub cell: (usize, usize),
+    /// Индекс комнаты, в которой стоит враг.
+    pub room: usize,
+}
+
+/// Сколько очков сложности достается комнате. Босс в этот бюджет не входит,
+/// на него тратится только свита.
+pub fn room_budget(role: RoomRole, depth: usize) -> u32 {
+    let depth = depth as u32;
+    match role {
+        | RoomRole::Common => 2 + depth,
+        | RoomRole::Treasure => 1 + depth / 2,
+        | RoomRole::Boss => depth,
+        | RoomRole::Start | RoomRole::Shop | RoomRole::Shrine => 0,
+    }
+}
+
+/// Расставляет врагов по комнатам. При одинаковых уровне и `seed`
+/// расстановка всегда одна и та же.
+pub fn plan_encounters(
+    rooms: &[Room],
+    grid: &DungeonNavGrid,
+    catalog: &EnemyCatalog,
+    depth: usize,
+    seed: u64,
+) -> Vec<Encounter> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let Some(table) = catalog.spawn_table(depth) else {
+        return Vec::new();
+    };
+    let start = rooms
+        .iter()
+        .find(|room| room.role == RoomRole::Start)
+        .map(|room| room.center())
+        .map(|(i, j)| (i as usize, j as usize));
+
+    let mut encounters = Vec::new();
+    for (index, room) in rooms.iter().enumerate() {
+        let mut cells = free_cells(room, grid, start, &mut rng);
+        let place = |archetype: &str, cells: &mut Vec<(usize, usize)>| {
+            cells.pop().map(|cell| Encounter {
+                archetype: archetype.to_string(),
+                cell,
+                room: index,
+            })
+        };
+
+        if room.role == RoomRole::Boss {
+            // Босс встает в центр комнаты, если там свободно
+            let (i, j) = room.center();
+            let center = (i as usize, j as usize);
+            if let Some(position) = cells.iter().position(|&cell| cell == center) {
+                let cell = cells.remove(position);
+                cells.push(cell);
+            }
+            if let Some(boss) = table.pick_boss(&mut rng) {
+                encounters.extend(place(boss, &mut cells));
+            }
+        }
+
+        let mut budget = room_budget(room.role, depth);
+        while budget > 0 && !cells.is_empty() {
+            let affordable = table
+                .enemies
+                .iter()
+                .filter(|(id, _)| catalog.archetypes[id].cost <= budget);
+            let Some(archetype) = pick(affordable, &mut rng) else {
+                break;
+            };
+            budget -= catalog.archetypes[archetype].cost.max(1);
+            encounters.extend(place(archetype, &mut cells));
+        }
+    }
+    encounters
+}
+
+/// Клетки комнаты, где может встать враг, в порядке заполнения с конца:
+/// сначала отмеченные шаблоном комнаты, потом остальные вперемешку.
+fn free_cells(
+    room: &Room,
+    grid: &DungeonNavGrid,
+    start: Option<(usize, usize)>,
+    rng: &mut impl Rng,
+) -> Vec<(usize, usize)> {
+    let near_door = |(i, j): (usize, usize)| {
+        let range = |k: usize| k.saturating_sub(DOOR_CLEARANCE)..=k + DOOR_CLEARANCE;
+        range(i).any(|i| {
+            range(j).any(|j| i < DUNGEON_ROW && j < DUNGEON_COLUMN && grid.is_door((i, j)))
+        })
+    };
+    let near_start = |(i, j): (usize, usize)| {
+        start.is_some_and(|(si, sj)| i.abs_diff(si) + j.abs_diff(sj) < START_CLEARANCE)
+    };
+
+    // Центр особой комнаты и отметки добычи заняты сундуками и алтарями
+    let (i, j) = room.center();
+    let center = (i as usize, j as usize);
+    let feature = matches!(
+        room.role,
+        RoomRole::Treasure | RoomRole::Shop | RoomRole::Shrine
+    );
+    let occupied = |cell: (usize, usize)| {
+        (feature && cell == center)
+            || room.markers.iter().any(|marker| {
+                marker.marker_type == MarkerType::Loot
+                    && (marker.i as usize, marker.j as usize) == cell
+            })
+    };
+
+    let mut cells: Vec<(usize, usize)> = room
+        .cells()
+        .into_iter()
+        .map(|(i, j)| (i as usize, j as usize))
+        .filter(|&cell| {
+            grid.is_walkable(cell) && !near_door(cell) && !near_start(cell) && !occupied(cell)
+        })
+        .collect();
+    cells.shuffle(rng);
+
+    let marked = |cell: &(usize, usize)| {
+        room.markers.iter().any(|marker| {
+            marker.marker_type == MarkerType::Enemy
+                && (marker.i as usize, marker.j as usize) == *cell
+        })
+    };
+    cells.sort_by_key(marked);
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::archetype::ENEMIES_PATH;
+    use crate::dungeon::level::{Level, LevelSettings};
+
+    #[test]
+    fn test_encounters() {
+        let catalog = EnemyCatalog::load(ENEMIES_PATH).unwrap();
+        let level = Level::<DUNGEON_ROW, DUNGEON_COLUMN>::new(&LevelSettings::default());
+        let grid = DungeonNavGrid::from_level(&level);
+        let rooms = &level.room_layer.rooms;
+        let depth = 3;
+
+        let encounters = plan_encounters(rooms, &grid, &catalog, depth, 42);
+        assert_eq!(
+            encounters,
+            plan_encounters(rooms, &grid, &catalog, depth, 42)
+        );
+
+        let start = rooms[0].center();
+        for (index, room) in rooms.iter().enumerate() {
+            let in_room: Vec<&Encounter> = encounters
+                .iter()
+                .filter(|encounter| encounter.room == index)
+                .collect();
+            let cost: u32 = in_room
+                .iter()
+                .map(|encounter| &catalog.archetypes[&encounter.archetype])
+                .filter(|archetype| !archetype.boss)
+                .map(|archetype| archetype.cost)
+                .sum();
+            assert!(cost <= room_budget(room.role, depth));
+
+            for encounter in in_room {
+                let (i, j) = encounter.cell;
+                assert!(room.cells().contains(&(i as i32, j as i32)));
+                assert!(grid.is_walkable(encounter.cell));
+                assert!(!grid.is_door(encounter.cell));
+                assert!(
+                    i.abs_diff(start.0 as usize) + j.abs_diff(start.1 as usize) >= START_CLEARANCE
+                );
+                assert_eq!(
+                    encounters
+                        .iter()
+                        .filter(|other| other.cell == encounter.cell)
+                        .count(),
+                    1,
+                    "two enemies in one cell"
+                );
+            }
+        }
+    }
+}
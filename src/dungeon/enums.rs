@@ -1,3 +1,5 @@
+use serde::Deserialize;
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum CornerType {
     TopLeft,
@@ -11,6 +13,13 @@ pub enum FloorType {
     Empthy,
     Room,
     Path,
+    Pit,
+}
+
+impl FloorType {
+    pub fn is_walkable(&self) -> bool {
+        matches!(self, FloorType::Room | FloorType::Path)
+    }
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -36,3 +45,50 @@ pub enum TileType {
     Wall(WallType),
     Door(DoorType),
 }
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PropType {
+    Empthy,
+    Barrel,
+    Crate,
+    BrokenWall,
+    Debris,
+}
+
+impl PropType {
+    /// Твердый проп занимает клетку: через него нельзя пройти.
+    pub fn is_solid(&self) -> bool {
+        matches!(
+            self,
+            PropType::Barrel | PropType::Crate | PropType::BrokenWall
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum RoomRole {
+    Common,
+    Start,
+    Boss,
+    Treasure,
+    Shop,
+    Shrine,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MarkerType {
+    Enemy,
+    Loot,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CorridorStyle {
+    /// Коридор в одну клетку из двух прямых отрезков.
+    LShaped,
+    /// Тот же Г-образный коридор шириной в две клетки.
+    Wide,
+    /// Коридор, идущий к цели по диагонали лесенкой.
+    Staircase,
+    /// Коридор, проложенный A* в обход чужих комнат.
+    Routed,
+}
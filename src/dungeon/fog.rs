@@ -0,0 +1,365 @@
+//! Туман войны: игрок видит только клетки в поле зрения, разведанные
+//! клетки остаются на экране затемненными, а неразведанные скрыты.
+
+use crate::prelude::*;
+
+use crate::dungeon::components::{Enemy, Player, Tile};
+use crate::dungeon::{DungeonNavGrid, DUNGEON_COLUMN, DUNGEON_ROW};
+use bevy::utils::HashMap;
+
+/// Как далеко игрок видит, в клетках.
+const SIGHT_RADIUS: usize = 5;
+/// Размер затемняющей завесы над разведанной клеткой.
+const VEIL_SIZE: Vec3 = Vec3::new(4.1, 8.0, 4.1);
+const VEIL_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+
+pub struct FogOfWarPlugin;
+
+impl Plugin for FogOfWarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FogOfWar>()
+            .init_resource::<FogVeils>()
+            .add_systems(
+                Update,
+                (update_fog, (hide_tiles, hide_enemies))
+                    .chain()
+                    .run_if(resource_exists::<DungeonNavGrid>()),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FogState {
+    /// Игрок здесь еще не бывал, клетка скрыта.
+    Unseen,
+    /// Клетку видели раньше, она видна затемненной.
+    Seen,
+    /// Клетка сейчас в поле зрения игрока.
+    Visible,
+}
+
+/// Что игрок знает о каждой клетке уровня.
+#[derive(Resource)]
+pub struct FogOfWar {
+    cells: [[FogState; DUNGEON_COLUMN]; DUNGEON_ROW],
+    /// Клетки игроков, из которых поле зрения считалось в последний раз.
+    origins: Vec<(usize, usize)>,
+    /// Клетки, состояние которых изменилось при последнем пересчете.
+    changed: Vec<(usize, usize)>,
+}
+
+impl Default for FogOfWar {
+    fn default() -> Self {
+        FogOfWar {
+            cells: [[FogState::Unseen; DUNGEON_COLUMN]; DUNGEON_ROW],
+            origins: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+impl FogOfWar {
+    /// Туман, в котором `explored` уже разведаны.
+    pub fn from_explored(explored: &[(usize, usize)]) -> Self {
+        let mut fog = FogOfWar::default();
+        for &(i, j) in explored {
+            if i < DUNGEON_ROW && j < DUNGEON_COLUMN {
+                fog.cells[i][j] = FogState::Seen;
+                fog.changed.push((i, j));
+            }
+        }
+        fog
+    }
+
+    /// Состояние клетки. Клетки за пределами уровня никогда не видны.
+    pub fn state(&self, (i, j): (usize, usize)) -> FogState {
+        self.cells
+            .get(i)
+            .and_then(|row| row.get(j))
+            .copied()
+            .unwrap_or(FogState::Unseen)
+    }
+
+    /// Все клетки, которые игрок когда-либо видел.
+    pub fn explored(&self) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for (i, row) in self.cells.iter().enumerate() {
+            for (j, &state) in row.iter().enumerate() {
+                if state != FogState::Unseen {
+                    cells.push((i, j));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Делает видимыми клетки `visible`, а остальные видимые переводит в разведанные.
+    fn reveal(&mut self, visible: &[(usize, usize)]) {
+        let mut cells = self.cells;
+        for state in cells.iter_mut().flatten() {
+            if *state == FogState::Visible {
+                *state = FogState::Seen;
+            }
+        }
+        for &(i, j) in visible {
+            cells[i][j] = FogState::Visible;
+        }
+
+        self.changed.clear();
+        for (i, (row, old)) in cells.iter().zip(&self.cells).enumerate() {
+            for (j, (state, old)) in row.iter().zip(old).enumerate() {
+                if state != old {
+                    self.changed.push((i, j));
+                }
+            }
+        }
+        self.cells = cells;
+    }
+}
+
+/// Затемняющие завесы над разведанными клетками.
+#[derive(Resource, Default)]
+pub(super) struct FogVeils {
+    veils: HashMap<(usize, usize), Entity>,
+    visuals: Option<(Handle<Mesh>, Handle<StandardMaterial>)>,
+}
+
+/// Пересчитывает поле зрения, когда кто-то из игроков переходит в другую клетку.
+/// В совместной игре видно все, что видит хотя бы один игрок.
+fn update_fog(
+    grid: Res<DungeonNavGrid>,
+    mut fog: ResMut<FogOfWar>,
+    players: Query<&Transform, With<Player>>,
+) {
+    let mut origins: Vec<(usize, usize)> = players
+        .iter()
+        .filter_map(|transform| grid.cell(transform.translation))
+        .collect();
+    origins.sort();
+    if origins.is_empty() || fog.origins == origins {
+        return;
+    }
+    let visible: Vec<(usize, usize)> = origins
+        .iter()
+        .flat_map(|&cell| grid.field_of_view(cell, SIGHT_RADIUS))
+        .collect();
+    fog.origins = origins;
+    fog.reveal(&visible);
+}
+
+/// Прячет неразведанные тайлы и накрывает завесой разведанные, но не видимые сейчас.
+pub(super) fn hide_tiles(
+    mut commands: Commands,
+    grid: Res<DungeonNavGrid>,
+    fog: Res<FogOfWar>,
+    mut veils: ResMut<FogVeils>,
+    mut tiles: Query<(Ref<Tile>, &mut Visibility)>,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+) {
+    for (tile, mut visibility) in &mut tiles {
+        if !fog.is_changed() && !tile.is_added() {
+            continue;
+        }
+        let state = grid
+            .cell(tile.0)
+            .map_or(FogState::Unseen, |cell| fog.state(cell));
+        let target = match state {
+            | FogState::Unseen => Visibility::Hidden,
+            | FogState::Seen | FogState::Visible => Visibility::Inherited,
+        };
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+
+    if !fog.is_changed() {
+        return;
+    }
+    if veils.visuals.is_none() {
+        if let (Some(mut meshes), Some(mut materials)) = (meshes, materials) {
+            veils.visuals = Some((
+                meshes.add(Mesh::from(shape::Box::new(
+                    VEIL_SIZE.x,
+                    VEIL_SIZE.y,
+                    VEIL_SIZE.z,
+                ))),
+                materials.add(StandardMaterial {
+                    base_color: VEIL_COLOR,
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..default()
+                }),
+            ));
+        }
+    }
+    let Some((mesh, material)) = veils.visuals.clone() else {
+        return;
+    };
+
+    // Завесы трогаем только над клетками, состояние которых изменилось
+    for &cell in &fog.changed {
+        let visibility = match fog.state(cell) {
+            | FogState::Seen => Visibility::Inherited,
+            | FogState::Unseen | FogState::Visible => Visibility::Hidden,
+        };
+        match veils.veils.get(&cell) {
+            | Some(&veil) => {
+                commands.entity(veil).insert(visibility);
+            }
+            | None => {
+                let veil = commands
+                    .spawn(PbrBundle {
+                        mesh: mesh.clone(),
+                        material: material.clone(),
+                        transform: Transform::from_translation(
+                            grid.position(cell) + Vec3::Y * VEIL_SIZE.y / 2.0,
+                        ),
+                        visibility,
+                        ..default()
+                    })
+                    .id();
+                veils.veils.insert(cell, veil);
+            }
+        }
+    }
+}
+
+/// Враги видны, только пока стоят в поле зрения игрока.
+fn hide_enemies(
+    grid: Res<DungeonNavGrid>,
+    fog: Res<FogOfWar>,
+    mut enemies: Query<(&Transform, &mut Visibility), With<Enemy>>,
+) {
+    for (transform, mut visibility) in &mut enemies {
+        let visible = grid
+            .cell(transform.translation)
+            .is_some_and(|cell| fog.state(cell) == FogState::Visible);
+        let target = if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// Уровень из двух комнат 3×3, разделенных пустотой, и коридора
+    /// вдоль первой комнаты без двери в нее.
+    fn grid() -> DungeonNavGrid {
+        let rooms = (1..4).flat_map(|i| (1..4).chain(8..11).map(move |j| (i, j)));
+        let corridor = (1..4).map(|j| (4, j));
+        testing::arena(rooms, corridor)
+    }
+
+    #[test]
+    fn test_fog() {
+        let mut app = testing::app();
+        app.add_plugins(FogOfWarPlugin).insert_resource(grid());
+        let player = app
+            .world
+            .spawn((Player, Transform::from_xyz(8.0, 0.0, 8.0)))
+            .id();
+        let near = app
+            .world
+            .spawn((Tile(Vec3::new(4.0, 0.0, 4.0)), Visibility::Inherited))
+            .id();
+        let far = app
+            .world
+            .spawn((Tile(Vec3::new(8.0, 0.0, 36.0)), Visibility::Inherited))
+            .id();
+        let enemy = app
+            .world
+            .spawn((
+                Enemy,
+                Transform::from_xyz(8.0, 0.0, 36.0),
+                Visibility::Inherited,
+            ))
+            .id();
+        app.update();
+
+        let fog = app.world.resource::<FogOfWar>();
+        assert_eq!(fog.state((2, 2)), FogState::Visible);
+        assert_eq!(fog.state((2, 9)), FogState::Unseen);
+        assert_eq!(fog.state((4, 2)), FogState::Unseen, "seen through the wall");
+        let visibility = |app: &App, entity| *app.world.get::<Visibility>(entity).unwrap();
+        assert_eq!(visibility(&app, near), Visibility::Inherited);
+        assert_eq!(visibility(&app, far), Visibility::Hidden);
+        assert_eq!(visibility(&app, enemy), Visibility::Hidden);
+
+        app.world.get_mut::<Transform>(player).unwrap().translation = Vec3::new(8.0, 0.0, 36.0);
+        app.update();
+
+        let fog = app.world.resource::<FogOfWar>();
+        assert_eq!(fog.state((2, 2)), FogState::Seen);
+        assert_eq!(fog.state((2, 9)), FogState::Visible);
+        assert_eq!(visibility(&app, near), Visibility::Inherited);
+        assert_eq!(visibility(&app, far), Visibility::Inherited);
+        assert_eq!(visibility(&app, enemy), Visibility::Inherited);
+
+        // Второй игрок в первой комнате: видно обе
+        app.world
+            .spawn((Player, Transform::from_xyz(8.0, 0.0, 8.0)));
+        app.update();
+        let fog = app.world.resource::<FogOfWar>();
+        assert_eq!(fog.state((2, 2)), FogState::Visible);
+        assert_eq!(fog.state((2, 9)), FogState::Visible);
+
+        let explored = FogOfWar::from_explored(&fog.explored());
+        assert_eq!(explored.state((2, 2)), FogState::Seen);
+        assert_eq!(explored.state((2, 9)), FogState::Seen);
+        assert_eq!(explored.state((7, 7)), FogState::Unseen);
+    }
+
+    #[test]
+    fn test_fog_veils() {
+        let mut app = testing::app();
+        app.add_plugins(FogOfWarPlugin)
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .insert_resource(grid());
+        let player = app
+            .world
+            .spawn((Player, Transform::from_xyz(8.0, 0.0, 8.0)))
+            .id();
+        app.update();
+
+        let veil = |app: &App, cell| app.world.resource::<FogVeils>().veils[&cell];
+        let visibility = |app: &App, entity| *app.world.get::<Visibility>(entity).unwrap();
+        assert_eq!(visibility(&app, veil(&app, (2, 2))), Visibility::Hidden);
+
+        app.world.get_mut::<Transform>(player).unwrap().translation = Vec3::new(8.0, 0.0, 36.0);
+        app.update();
+        let seen = veil(&app, (2, 2));
+        assert_eq!(visibility(&app, seen), Visibility::Inherited);
+        assert_eq!(visibility(&app, veil(&app, (2, 9))), Visibility::Hidden);
+        let changed = app
+            .world
+            .entity(seen)
+            .get_ref::<Visibility>()
+            .unwrap()
+            .last_changed();
+
+        // Шаг внутри комнаты не меняет тумана, завесы не трогаются
+        app.world.get_mut::<Transform>(player).unwrap().translation = Vec3::new(8.0, 0.0, 40.0);
+        app.update();
+        let veil_changed = app
+            .world
+            .entity(seen)
+            .get_ref::<Visibility>()
+            .unwrap()
+            .last_changed();
+        assert_eq!(veil_changed, changed);
+        assert_eq!(
+            app.world.resource::<FogVeils>().veils.len(),
+            app.world.resource::<FogOfWar>().explored().len()
+        );
+    }
+}
@@ -1,22 +1,100 @@
-mod layer;
+pub mod layer;
+pub mod pathfinding;
+pub mod visibility;
 
+pub use layer::prop::PropLayer;
 pub use layer::room::RoomLayer;
 pub use layer::wall::WallLayer;
 
+use crate::dungeon::enums::CorridorStyle;
+use bevy::prelude::Resource;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Настройки генерации уровня.
+#[derive(Resource, Clone)]
+pub struct LevelSettings {
+    pub room_amount: usize,
+    pub scale: f32,
+    /// Глубина подземелья, от нее зависит, какие враги встречаются.
+    pub depth: usize,
+    /// Зерно, из которого растут комнаты, пропы и враги: с одним зерном
+    /// уровень всегда получается одинаковым.
+    pub seed: u64,
+}
+
+impl Default for LevelSettings {
+    fn default() -> Self {
+        LevelSettings {
+            room_amount: 6,
+            scale: 4.,
+            depth: 1,
+            seed: rand::random(),
+        }
+    }
+}
+
+/// Стили коридоров, из которых зерно выбирает стиль уровня.
+const CORRIDOR_STYLES: [CorridorStyle; 4] = [
+    CorridorStyle::LShaped,
+    CorridorStyle::Wide,
+    CorridorStyle::Staircase,
+    CorridorStyle::Routed,
+];
+
+impl LevelSettings {
+    /// Стиль коридоров уровня. Первый уровень соединен простыми Г-образными
+    /// коридорами, глубже стиль выбирает зерно.
+    pub fn corridor_style(&self) -> CorridorStyle {
+        if self.depth <= 1 {
+            return CorridorStyle::LShaped;
+        }
+        CORRIDOR_STYLES[(self.seed % CORRIDOR_STYLES.len() as u64) as usize]
+    }
+}
+
 pub struct Level<const COLUMN: usize, const ROW: usize> {
     pub room_layer: RoomLayer<COLUMN, ROW>,
     pub wall_layer: WallLayer<COLUMN, ROW>,
+    pub prop_layer: PropLayer<COLUMN, ROW>,
 }
 
 impl<const COLUMN: usize, const ROW: usize> Level<COLUMN, ROW> {
-    pub fn new() -> Self {
-        const ROOM_AMOUNT: usize = 6;
-        const ROOM_LAYER_SCALE: f32 = 4.;
-        let room_layer = RoomLayer::new(ROOM_LAYER_SCALE, ROOM_AMOUNT);
-        let wall_layer = WallLayer::new(ROOM_LAYER_SCALE, room_layer.clone());
+    pub fn new(settings: &LevelSettings) -> Self {
+        let mut rng = StdRng::seed_from_u64(settings.seed);
+        let room_layer = RoomLayer::new(
+            &mut rng,
+            settings.scale,
+            settings.room_amount,
+            settings.corridor_style(),
+        );
+        let wall_layer = WallLayer::new(settings.scale, room_layer.clone());
+        let prop_layer = PropLayer::new(&mut rng, settings.scale, &room_layer, &wall_layer);
         Level {
             room_layer,
             wall_layer,
+            prop_layer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corridor_style() {
+        let settings = |depth, seed| LevelSettings {
+            depth,
+            seed,
+            ..LevelSettings::default()
+        };
+        assert!(settings(1, 3).corridor_style() == CorridorStyle::LShaped);
+        for style in CORRIDOR_STYLES {
+            assert!(
+                (0..4).any(|seed| settings(2, seed).corridor_style() == style),
+                "a style is never picked"
+            );
         }
     }
 }
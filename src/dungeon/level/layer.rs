@@ -1,3 +1,7 @@
-mod base;
+pub mod base;
+mod corridor;
+pub mod prop;
 pub mod room;
+pub mod shape;
+pub mod template;
 pub mod wall;
@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::ops::{Index, IndexMut};
 
 #[allow(dead_code)]
@@ -22,6 +23,15 @@ impl<T, const ROW: usize, const COLUMN: usize> Layer<T, ROW, COLUMN> {
         ((i as f32) * self.scale, (j as f32) * self.scale)
     }
 
+    /// Клетка, в которую попадает точка `(x, z)`, обратное к [`Layer::get_coordiante`].
+    pub fn get_cell(&self, x: f32, z: f32) -> Option<(usize, usize)> {
+        let (i, j) = ((x / self.scale).round(), (z / self.scale).round());
+        if i < 0. || j < 0. || i >= ROW as f32 || j >= COLUMN as f32 {
+            return None;
+        }
+        Some((i as usize, j as usize))
+    }
+
     #[allow(dead_code)]
     pub fn row(&self) -> usize {
         ROW
@@ -32,6 +42,78 @@ impl<T, const ROW: usize, const COLUMN: usize> Layer<T, ROW, COLUMN> {
         COLUMN
     }
 
+    /// Соседи клетки по четырем сторонам, не выходящие за границы слоя.
+    pub fn neighbours(&self, i: usize, j: usize) -> impl Iterator<Item = (usize, usize)> {
+        [
+            (i.checked_sub(1), Some(j)),
+            (Some(i + 1), Some(j)),
+            (Some(i), j.checked_sub(1)),
+            (Some(i), Some(j + 1)),
+        ]
+        .into_iter()
+        .filter_map(|neighbour| match neighbour {
+            | (Some(i), Some(j)) if i < ROW && j < COLUMN => Some((i, j)),
+            | _ => None,
+        })
+    }
+
+    /// Проверяет, что все клетки, для которых `passable` истинно, связаны между собой.
+    pub fn is_connected(&self, passable: impl Fn((usize, usize)) -> bool) -> bool {
+        let cells: Vec<(usize, usize)> = (0..ROW)
+            .flat_map(|i| (0..COLUMN).map(move |j| (i, j)))
+            .filter(|&cell| passable(cell))
+            .collect();
+        let Some(&start) = cells.first() else {
+            return true;
+        };
+
+        let mut visited = [[false; COLUMN]; ROW];
+        let mut queue = VecDeque::from([start]);
+        visited[start.0][start.1] = true;
+        let mut reached = 1;
+
+        while let Some((i, j)) = queue.pop_front() {
+            for (ni, nj) in self.neighbours(i, j) {
+                if !visited[ni][nj] && passable((ni, nj)) {
+                    visited[ni][nj] = true;
+                    reached += 1;
+                    queue.push_back((ni, nj));
+                }
+            }
+        }
+
+        reached == cells.len()
+    }
+
+    /// Номер связной области для каждой клетки, для которой `passable` истинно,
+    /// и `None` для остальных.
+    pub fn components(
+        &self,
+        passable: impl Fn((usize, usize)) -> bool,
+    ) -> Layer<Option<usize>, ROW, COLUMN> {
+        let mut components = Layer::new(None, self.scale);
+        let mut count = 0;
+        for i in 0..ROW {
+            for j in 0..COLUMN {
+                if components[(i, j)].is_some() || !passable((i, j)) {
+                    continue;
+                }
+                components[(i, j)] = Some(count);
+                let mut queue = VecDeque::from([(i, j)]);
+                while let Some((i, j)) = queue.pop_front() {
+                    for neighbour in self.neighbours(i, j) {
+                        if components[neighbour].is_none() && passable(neighbour) {
+                            components[neighbour] = Some(count);
+                            queue.push_back(neighbour);
+                        }
+                    }
+                }
+                count += 1;
+            }
+        }
+        components
+    }
+
     pub fn iter(&self) -> LayerIterator<'_, T, ROW, COLUMN> {
         LayerIterator::new(self)
     }
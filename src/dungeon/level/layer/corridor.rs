@@ -0,0 +1,118 @@
+use super::base::Layer;
+use super::room::{apply_column_tunnel, apply_row_tunnel};
+use crate::dungeon::enums::{CorridorStyle, FloorType};
+use crate::dungeon::level::pathfinding::a_star;
+use rand::Rng;
+use std::cmp::Ordering;
+
+/// Цена прокладки коридора через пустую клетку.
+const DIG_COST: u32 = 2;
+/// Надбавка за клетку, примыкающую к комнате: чтобы коридор не тянулся вдоль стен.
+const ROOM_SIDE_COST: u32 = 4;
+/// Цена прохода сквозь комнату, которую коридор не соединяет.
+const FOREIGN_ROOM_COST: u32 = 50;
+
+/// Прокладывает коридор от `from` до `to` в стиле `style`.
+///
+/// `is_foreign` отмечает клетки чужих комнат, которые коридор
+/// стиля [`CorridorStyle::Routed`] старается обойти.
+pub fn dig_corridor<const ROW: usize, const COLUMN: usize>(
+    rng: &mut impl Rng,
+    layer: &mut Layer<FloorType, ROW, COLUMN>,
+    style: CorridorStyle,
+    from: (i32, i32),
+    to: (i32, i32),
+    is_foreign: impl Fn((usize, usize)) -> bool,
+) {
+    match style {
+        | CorridorStyle::LShaped => {
+            let row_first = rng.gen_bool(0.5);
+            dig_l_shaped(layer, from, to, row_first, 0);
+        }
+        | CorridorStyle::Wide => {
+            let row_first = rng.gen_bool(0.5);
+            dig_l_shaped(layer, from, to, row_first, 0);
+            dig_l_shaped(layer, from, to, row_first, 1);
+        }
+        | CorridorStyle::Staircase => dig_staircase(rng, layer, from, to),
+        | CorridorStyle::Routed => {
+            let start = (from.0 as usize, from.1 as usize);
+            let goal = (to.0 as usize, to.1 as usize);
+            let path = a_star(layer, start, goal, |_, cell| {
+                Some(match layer[cell] {
+                    | FloorType::Room if is_foreign(cell) => FOREIGN_ROOM_COST,
+                    | FloorType::Room | FloorType::Path | FloorType::Pit => 1,
+                    | FloorType::Empthy => {
+                        let near_room = layer
+                            .neighbours(cell.0, cell.1)
+                            .any(|neighbour| layer[neighbour] == FloorType::Room);
+                        DIG_COST + if near_room { ROOM_SIDE_COST } else { 0 }
+                    }
+                })
+            });
+            match path {
+                | Some(path) => {
+                    for cell in path {
+                        dig(layer, cell);
+                    }
+                }
+                | None => dig_l_shaped(layer, from, to, true, 0),
+            }
+        }
+    }
+}
+
+fn dig<const ROW: usize, const COLUMN: usize>(
+    layer: &mut Layer<FloorType, ROW, COLUMN>,
+    cell: (usize, usize),
+) {
+    if layer[cell] == FloorType::Empthy {
+        layer[cell] = FloorType::Path;
+    }
+}
+
+/// Г-образный коридор, сдвинутый на `offset` клеток: второй проход
+/// со сдвигом на единицу делает коридор шириной в две клетки.
+fn dig_l_shaped<const ROW: usize, const COLUMN: usize>(
+    layer: &mut Layer<FloorType, ROW, COLUMN>,
+    (i1, j1): (i32, i32),
+    (i2, j2): (i32, i32),
+    row_first: bool,
+    offset: i32,
+) {
+    let shift_i = |i: i32| (i + offset).min(ROW as i32 - 2);
+    let shift_j = |j: i32| (j + offset).min(COLUMN as i32 - 2);
+    if row_first {
+        apply_row_tunnel(layer, i1, i2, shift_j(j1));
+        apply_column_tunnel(layer, shift_i(i2), j1, j2);
+    } else {
+        apply_column_tunnel(layer, shift_i(i1), j1, j2);
+        apply_row_tunnel(layer, i1, i2, shift_j(j2));
+    }
+}
+
+/// Коридор, который шагает по той оси, где до цели дальше,
+/// и на диагонали чередует шаги лесенкой.
+fn dig_staircase<const ROW: usize, const COLUMN: usize>(
+    rng: &mut impl Rng,
+    layer: &mut Layer<FloorType, ROW, COLUMN>,
+    from: (i32, i32),
+    to: (i32, i32),
+) {
+    let (mut i, mut j) = from;
+    dig(layer, (i as usize, j as usize));
+    while (i, j) != to {
+        let (di, dj) = (to.0 - i, to.1 - j);
+        let step_row = match di.abs().cmp(&dj.abs()) {
+            | Ordering::Greater => true,
+            | Ordering::Less => false,
+            | Ordering::Equal => rng.gen_bool(0.5),
+        };
+        if step_row {
+            i += di.signum();
+        } else {
+            j += dj.signum();
+        }
+        dig(layer, (i as usize, j as usize));
+    }
+}
@@ -0,0 +1,151 @@
+use super::base::Layer;
+use super::room::RoomLayer;
+use super::wall::WallLayer;
+use crate::dungeon::enums::{FloorType, PropType, TileType, WallType};
+use rand::Rng;
+
+/// Вероятность поставить проп в угол комнаты.
+const CORNER_DENSITY: f64 = 0.6;
+/// Вероятность поставить проп вдоль стены.
+const WALL_DENSITY: f64 = 0.2;
+
+#[derive(Clone, Copy)]
+pub struct Prop {
+    pub i: usize,
+    pub j: usize,
+    pub prop_type: PropType,
+    /// Стена, к которой прижат проп.
+    pub anchor: WallType,
+}
+
+pub struct PropLayer<const ROW: usize, const COLUMN: usize> {
+    pub layer: Layer<PropType, ROW, COLUMN>,
+    pub props: Vec<Prop>,
+}
+
+impl<const ROW: usize, const COLUMN: usize> PropLayer<ROW, COLUMN> {
+    pub fn new(
+        rng: &mut impl Rng,
+        scale: f32,
+        room_layer: &RoomLayer<ROW, COLUMN>,
+        wall_layer: &WallLayer<ROW, COLUMN>,
+    ) -> PropLayer<ROW, COLUMN> {
+        let mut layer = Layer::new(PropType::Empthy, scale);
+        let mut props = Vec::new();
+
+        for i in 0..ROW {
+            for j in 0..COLUMN {
+                let TileType::Wall(anchor) = wall_layer.layer[(i, j)] else {
+                    continue;
+                };
+                if !is_free_spot(room_layer, wall_layer, i, j) {
+                    continue;
+                }
+
+                let (density, prop_type) = match anchor {
+                    | WallType::InternalCorner(_) => (CORNER_DENSITY, corner_prop(rng)),
+                    | _ => (WALL_DENSITY, wall_prop(rng)),
+                };
+                if !rng.gen_bool(density) {
+                    continue;
+                }
+                if prop_type.is_solid() && !is_connected(room_layer, &layer, Some((i, j))) {
+                    continue;
+                }
+
+                layer[(i, j)] = prop_type;
+                props.push(Prop {
+                    i,
+                    j,
+                    prop_type,
+                    anchor,
+                });
+            }
+        }
+
+        PropLayer { layer, props }
+    }
+}
+
+fn corner_prop(rng: &mut impl Rng) -> PropType {
+    match rng.gen_range(0..3) {
+        | 0 => PropType::Crate,
+        | _ => PropType::Barrel,
+    }
+}
+
+fn wall_prop(rng: &mut impl Rng) -> PropType {
+    match rng.gen_range(0..6) {
+        | 0 | 1 => PropType::Barrel,
+        | 2 => PropType::Crate,
+        | 3 => PropType::BrokenWall,
+        | _ => PropType::Debris,
+    }
+}
+
+/// Клетка комнаты, на которой проп не перекроет дверь, вход в коридор,
+/// центр комнаты или точку спауна из шаблона.
+fn is_free_spot<const ROW: usize, const COLUMN: usize>(
+    room_layer: &RoomLayer<ROW, COLUMN>,
+    wall_layer: &WallLayer<ROW, COLUMN>,
+    i: usize,
+    j: usize,
+) -> bool {
+    if room_layer.layer[(i, j)] != FloorType::Room {
+        return false;
+    }
+    let cell = (i as i32, j as i32);
+    if room_layer.rooms.iter().any(|room| {
+        room.center() == cell
+            || room
+                .markers
+                .iter()
+                .any(|marker| (marker.i, marker.j) == cell)
+    }) {
+        return false;
+    }
+    room_layer.layer.neighbours(i, j).all(|neighbour| {
+        room_layer.layer[neighbour] != FloorType::Path
+            && !matches!(wall_layer.layer[neighbour], TileType::Door(_))
+    })
+}
+
+/// Проверяет, что все проходимые клетки уровня связаны между собой,
+/// если дополнительно занять клетку `blocked`.
+fn is_connected<const ROW: usize, const COLUMN: usize>(
+    room_layer: &RoomLayer<ROW, COLUMN>,
+    prop_layer: &Layer<PropType, ROW, COLUMN>,
+    blocked: Option<(usize, usize)>,
+) -> bool {
+    room_layer.layer.is_connected(|cell| {
+        room_layer.layer[cell].is_walkable()
+            && !prop_layer[cell].is_solid()
+            && Some(cell) != blocked
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::enums::CorridorStyle;
+
+    #[test]
+    fn test_props_keep_doors_and_paths() -> Result<(), String> {
+        for _ in 0..50 {
+            let mut rng = rand::thread_rng();
+            let room_layer = RoomLayer::<15, 15>::new(&mut rng, 4., 6, CorridorStyle::LShaped);
+            let wall_layer = WallLayer::new(4., room_layer.clone());
+            let prop_layer = PropLayer::new(&mut rng, 4., &room_layer, &wall_layer);
+
+            for prop in prop_layer.props.iter() {
+                if !is_free_spot(&room_layer, &wall_layer, prop.i, prop.j) {
+                    return Err(format!("prop at ({}, {}) blocks a passage", prop.i, prop.j));
+                }
+            }
+            if !is_connected(&room_layer, &prop_layer.layer, None) {
+                return Err("props split the level".to_string());
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
 use super::base::Layer;
-use crate::dungeon::enums::FloorType;
-use rand::{rngs::ThreadRng, Rng};
+use super::corridor::dig_corridor;
+use super::shape::RoomShape;
+use super::template::{apply_templates, Marker};
+use crate::dungeon::enums::{CornerType, CorridorStyle, FloorType, RoomRole};
+use rand::Rng;
 use std::cmp::{max, min};
+use std::collections::VecDeque;
 use std::fmt;
 
 #[derive(Clone)]
@@ -11,57 +15,104 @@ pub struct RoomLayer<const ROW: usize, const COLUMN: usize> {
 }
 
 impl<const ROW: usize, const COLUMN: usize> RoomLayer<ROW, COLUMN> {
-    pub fn new(scale: f32, room_amount: usize) -> RoomLayer<ROW, COLUMN> {
+    pub fn new(
+        rng: &mut impl Rng,
+        scale: f32,
+        room_amount: usize,
+        corridor_style: CorridorStyle,
+    ) -> RoomLayer<ROW, COLUMN> {
         let mut layer = Layer::new(FloorType::Empthy, scale);
-        let rooms = generate_rooms(ROW, COLUMN, room_amount);
+        let mut rooms = generate_rooms(rng, ROW, COLUMN, room_amount);
 
-        let mut rng = rand::thread_rng();
-
-        let mut prev_i: i32 = 0;
-        let mut prev_j: i32 = 0;
-
-        for room in rooms.iter() {
-            apply_room_to_map(&mut layer, &room);
-
-            let (new_i, new_j) = room.center();
-            if rng.gen_range(1..=2) == 1 {
-                apply_row_tunnel(&mut layer, prev_i, new_i, prev_j);
-                apply_column_tunnel(&mut layer, new_i, prev_j, new_j);
-            } else {
-                apply_column_tunnel(&mut layer, prev_i, prev_j, new_j);
-                apply_row_tunnel(&mut layer, prev_i, new_i, new_j);
+        let mut owners = Layer::<Option<usize>, ROW, COLUMN>::new(None, scale);
+        for (index, room) in rooms.iter().enumerate() {
+            apply_room_to_map(&mut layer, room);
+            for (i, j) in room.cells() {
+                owners[(i as usize, j as usize)] = Some(index);
             }
-            (prev_i, prev_j) = (new_i, new_j);
         }
 
+        let mut prev = (0, 0);
+        for (index, room) in rooms.iter().enumerate() {
+            let next = room.center();
+            let is_foreign =
+                |cell| owners[cell].is_some_and(|owner| owner != index && owner + 1 != index);
+            dig_corridor(rng, &mut layer, corridor_style, prev, next, is_foreign);
+            prev = next;
+        }
+
+        assign_roles(&mut rooms, &layer);
+        apply_templates(rng, &mut layer, &mut rooms);
+
         RoomLayer { layer, rooms }
     }
 }
 
+/// Комната на слое. `i`, `j`, `row` и `column` задают описывающий прямоугольник,
+/// сами клетки комнаты определяет `shape`.
 #[derive(Clone)]
 pub struct Room {
     pub i: i32,
     pub j: i32,
     pub row: i32,
     pub column: i32,
+    pub shape: RoomShape,
+    pub role: RoomRole,
+    pub markers: Vec<Marker>,
 }
 
 impl Room {
+    #[cfg(test)]
     pub fn new(i: i32, j: i32, row: i32, column: i32) -> Room {
-        Room { i, j, row, column }
+        Room::with_shape(i, j, RoomShape::Rectangle { row, column })
     }
 
-    pub fn intersect(&self, other: &Room) -> bool {
-        let diff_i = (self.i - other.i).abs() * 2 - 3;
-        let diff_j = (self.j - other.j).abs() * 2 - 3;
-        let rooms_row = self.row + other.row;
-        let rooms_column = self.column + other.column;
+    pub fn with_shape(i: i32, j: i32, shape: RoomShape) -> Room {
+        let (row, column) = shape.size();
+        Room {
+            i,
+            j,
+            row,
+            column,
+            shape,
+            role: RoomRole::Common,
+            markers: Vec::new(),
+        }
+    }
+
+    pub fn cells(&self) -> Vec<(i32, i32)> {
+        self.shape
+            .cells()
+            .into_iter()
+            .map(|(i, j)| (self.i + i, self.j + j))
+            .collect()
+    }
+
+    /// Есть ли у комнат клетки, отстоящие друг от друга не больше чем на `margin`.
+    pub fn is_near(&self, other: &Room, margin: i32) -> bool {
+        let bounds_near = self.i - margin <= other.i + other.row
+            && other.i - margin <= self.i + self.row
+            && self.j - margin <= other.j + other.column
+            && other.j - margin <= self.j + self.column;
+        if !bounds_near {
+            return false;
+        }
 
-        diff_i <= rooms_row && diff_j <= rooms_column
+        let other_cells = other.cells();
+        self.cells().iter().any(|&(i, j)| {
+            other_cells
+                .iter()
+                .any(|&(oi, oj)| (i - oi).abs() <= margin && (j - oj).abs() <= margin)
+        })
     }
 
     pub fn center(&self) -> (i32, i32) {
-        ((self.i + self.row / 2), (self.j + self.column / 2))
+        let (i, j) = self.shape.center();
+        (self.i + i, self.j + j)
+    }
+
+    pub fn area(&self) -> i32 {
+        self.shape.cells().len() as i32
     }
 }
 
@@ -79,11 +130,7 @@ pub fn apply_room_to_map<const ROW: usize, const COLUMN: usize>(
     layer: &mut Layer<FloorType, ROW, COLUMN>,
     room: &Room,
 ) {
-    for i in room.i..=room.i + room.row {
-        for j in room.j..=room.j + room.column {
-            layer[(i as usize, j as usize)] = FloorType::Room;
-        }
-    }
+    room.shape.apply_to_map(layer, (room.i, room.j));
 }
 
 pub fn apply_row_tunnel<const ROW: usize, const COLUMN: usize>(
@@ -112,32 +159,70 @@ pub fn apply_column_tunnel<const ROW: usize, const COLUMN: usize>(
     }
 }
 
+/// Доля прямоугольных комнат среди сгенерированных, остальные делятся поровну
+/// между Г-образными, круглыми и крестообразными.
+const RECTANGLE_CHANCE: f64 = 0.55;
+
+fn get_random_shape(rng: &mut impl Rng, min_size: i32, max_size: i32) -> RoomShape {
+    let row = rng.gen_range(min_size..=max_size);
+    let column = rng.gen_range(min_size..=max_size);
+    if rng.gen_bool(RECTANGLE_CHANCE) {
+        return RoomShape::Rectangle { row, column };
+    }
+
+    match rng.gen_range(0..3) {
+        | 0 => {
+            let corners = [
+                CornerType::TopLeft,
+                CornerType::TopRight,
+                CornerType::BottomLeft,
+                CornerType::BottomRight,
+            ];
+            RoomShape::LShape {
+                row,
+                column,
+                cut_row: rng.gen_range(2..row),
+                cut_column: rng.gen_range(2..column),
+                corner: corners[rng.gen_range(0..corners.len())],
+            }
+        }
+        | 1 => RoomShape::Circle {
+            radius: row.min(column) / 2,
+        },
+        | _ => RoomShape::cross(row, column, 3),
+    }
+}
+
 fn get_random_room(
-    rng: &mut ThreadRng,
+    rng: &mut impl Rng,
     layer_row: i32,
     layer_column: i32,
     min_size: i32,
     max_size: i32,
 ) -> Room {
-    let room_row = rng.gen_range(min_size..=max_size);
-    let room_column = rng.gen_range(min_size..=max_size);
+    let shape = get_random_shape(rng, min_size, max_size);
+    let (room_row, room_column) = shape.size();
     let i = rng.gen_range(2..(layer_row - room_row)) - 1;
     let j = rng.gen_range(2..(layer_column - room_column)) - 1;
-    Room::new(i, j, room_row, room_column)
+    Room::with_shape(i, j, shape)
 }
 
-pub fn generate_rooms(row: usize, column: usize, room_amount: usize) -> Vec<Room> {
+pub fn generate_rooms(
+    rng: &mut impl Rng,
+    row: usize,
+    column: usize,
+    room_amount: usize,
+) -> Vec<Room> {
     let mut rooms: Vec<Room> = Vec::new();
     const MIN_SIZE: i32 = 3;
     const MAX_SIZE: i32 = 4;
 
-    let mut rng = rand::thread_rng();
     for _ in 0..room_amount * 4 {
-        let new_room = get_random_room(&mut rng, row as i32, column as i32, MIN_SIZE, MAX_SIZE);
+        let new_room = get_random_room(rng, row as i32, column as i32, MIN_SIZE, MAX_SIZE);
 
         if rooms
             .iter()
-            .all(|other_room| !new_room.intersect(other_room))
+            .all(|other_room| !new_room.is_near(other_room, 1))
         {
             rooms.push(new_room);
         }
@@ -145,6 +230,83 @@ pub fn generate_rooms(row: usize, column: usize, room_amount: usize) -> Vec<Room
     return rooms;
 }
 
+/// Ключ, по которому выбирается комната под роль: берется комната с наименьшим.
+type RoomKey = fn(&Room, usize) -> (i64, i64);
+
+/// Раздает комнатам роли по положению в графе уровня и по размеру.
+///
+/// Стартом служит первая комната цепочки, босс сидит в самой дальней от старта
+/// (при равенстве в самой большой). Побочные роли выдаются, только пока
+/// на уровне остается больше двух обычных комнат: сокровищница достается самой
+/// маленькой комнате, магазин самой близкой к старту, алтарь самой большой.
+pub fn assign_roles<const ROW: usize, const COLUMN: usize>(
+    rooms: &mut [Room],
+    layer: &Layer<FloorType, ROW, COLUMN>,
+) {
+    let Some(start) = rooms.first() else {
+        return;
+    };
+    let distances = floor_distances(layer, start.center());
+    let distance = |room: &Room| {
+        let (i, j) = room.center();
+        distances[(i as usize, j as usize)]
+    };
+
+    rooms[0].role = RoomRole::Start;
+    let mut rest: Vec<usize> = (1..rooms.len()).collect();
+    rest.sort_by_key(|&k| (distance(&rooms[k]), rooms[k].area()));
+
+    if let Some(boss) = rest.pop() {
+        rooms[boss].role = RoomRole::Boss;
+    }
+
+    let side_rooms: [(RoomRole, RoomKey); 3] = [
+        (RoomRole::Treasure, |room, distance| {
+            (room.area() as i64, -(distance as i64))
+        }),
+        (RoomRole::Shop, |room, distance| {
+            (distance as i64, room.area() as i64)
+        }),
+        (RoomRole::Shrine, |room, distance| {
+            (-(room.area() as i64), distance as i64)
+        }),
+    ];
+    for (role, key) in side_rooms {
+        if rest.len() <= 2 {
+            break;
+        }
+        let (position, _) = rest
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &k)| key(&rooms[k], distance(&rooms[k])))
+            .unwrap();
+        rooms[rest.remove(position)].role = role;
+    }
+}
+
+/// Расстояние в клетках по полу от `start` до каждой клетки уровня.
+/// Недостижимые клетки получают `usize::MAX`.
+fn floor_distances<const ROW: usize, const COLUMN: usize>(
+    layer: &Layer<FloorType, ROW, COLUMN>,
+    (i, j): (i32, i32),
+) -> Layer<usize, ROW, COLUMN> {
+    let mut distances = Layer::new(usize::MAX, layer.scale);
+    let start = (i as usize, j as usize);
+    distances[start] = 0;
+    let mut queue = VecDeque::from([start]);
+
+    while let Some((i, j)) = queue.pop_front() {
+        for neighbour in layer.neighbours(i, j) {
+            if layer[neighbour].is_walkable() && distances[neighbour] == usize::MAX {
+                distances[neighbour] = distances[(i, j)] + 1;
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    distances
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -163,18 +325,39 @@ mod tests {
         ]
         .iter()
         .try_for_each(|(room1, room2, expected)| {
-            if room1.intersect(room2) == *expected {
+            if room1.is_near(room2, 0) == *expected {
                 Ok(())
             } else {
                 Err(format!(
-                    "{}.intersect({}) result: {}, expected: {}",
+                    "{}.is_near({}, 0) result: {}, expected: {}",
                     room1,
                     room2,
-                    room1.intersect(room2),
+                    room1.is_near(room2, 0),
                     expected
                 ))
             }
         })?;
         Ok(())
     }
+
+    #[test]
+    fn test_roles() -> Result<(), String> {
+        for _ in 0..50 {
+            let room_layer =
+                RoomLayer::<15, 15>::new(&mut rand::thread_rng(), 4., 6, CorridorStyle::LShaped);
+            let rooms = &room_layer.rooms;
+            let count = |role| rooms.iter().filter(|room| room.role == role).count();
+
+            if rooms[0].role != RoomRole::Start || count(RoomRole::Start) != 1 {
+                return Err(format!("{} is not the only start room", rooms[0]));
+            }
+            if rooms.len() > 1 && count(RoomRole::Boss) != 1 {
+                return Err("level without a boss room".to_string());
+            }
+            if rooms.len() > 3 && count(RoomRole::Common) < 2 {
+                return Err("side rooms took every common room".to_string());
+            }
+        }
+        Ok(())
+    }
 }
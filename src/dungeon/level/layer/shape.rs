@@ -0,0 +1,180 @@
+use super::base::Layer;
+use crate::dungeon::enums::{CornerType, FloorType};
+
+/// Форма комнаты в локальных координатах. Клетка `(0, 0)` — нижний левый
+/// угол описывающего прямоугольника, `row` и `column`, как и у [`Room`],
+/// включительные: прямоугольник `row = 3` занимает четыре клетки.
+///
+/// [`Room`]: super::room::Room
+#[derive(Clone)]
+pub enum RoomShape {
+    Rectangle {
+        row: i32,
+        column: i32,
+    },
+    /// Прямоугольник, из угла `corner` которого вырезано `cut_row` на `cut_column` клеток.
+    LShape {
+        row: i32,
+        column: i32,
+        cut_row: i32,
+        cut_column: i32,
+        corner: CornerType,
+    },
+    Circle {
+        radius: i32,
+    },
+    /// Объединение фигур, каждая сдвинута на `(i, j)`.
+    Composite(Vec<(i32, i32, RoomShape)>),
+}
+
+impl RoomShape {
+    /// Крест из двух пересекающихся полос шириной `arm` клеток.
+    pub fn cross(row: i32, column: i32, arm: i32) -> RoomShape {
+        RoomShape::Composite(vec![
+            (
+                0,
+                (column + 1 - arm) / 2,
+                RoomShape::Rectangle {
+                    row,
+                    column: arm - 1,
+                },
+            ),
+            (
+                (row + 1 - arm) / 2,
+                0,
+                RoomShape::Rectangle {
+                    row: arm - 1,
+                    column,
+                },
+            ),
+        ])
+    }
+
+    pub fn is_rectangle(&self) -> bool {
+        matches!(self, RoomShape::Rectangle { .. })
+    }
+
+    pub fn cells(&self) -> Vec<(i32, i32)> {
+        match self {
+            | RoomShape::Rectangle { row, column } => rectangle(*row, *column).collect(),
+            | RoomShape::LShape {
+                row,
+                column,
+                cut_row,
+                cut_column,
+                corner,
+            } => {
+                let in_cut_row = |i: i32| match corner {
+                    | CornerType::BottomLeft | CornerType::BottomRight => i < *cut_row,
+                    | CornerType::TopLeft | CornerType::TopRight => i > row - cut_row,
+                };
+                let in_cut_column = |j: i32| match corner {
+                    | CornerType::BottomLeft | CornerType::TopLeft => j < *cut_column,
+                    | CornerType::BottomRight | CornerType::TopRight => j > column - cut_column,
+                };
+                rectangle(*row, *column)
+                    .filter(|&(i, j)| !(in_cut_row(i) && in_cut_column(j)))
+                    .collect()
+            }
+            | RoomShape::Circle { radius } => rectangle(radius * 2, radius * 2)
+                .filter(|&(i, j)| {
+                    (i - radius).pow(2) + (j - radius).pow(2) <= radius * radius + radius
+                })
+                .collect(),
+            | RoomShape::Composite(shapes) => {
+                let mut cells: Vec<(i32, i32)> = shapes
+                    .iter()
+                    .flat_map(|(di, dj, shape)| {
+                        shape
+                            .cells()
+                            .into_iter()
+                            .map(move |(i, j)| (i + di, j + dj))
+                    })
+                    .collect();
+                cells.sort();
+                cells.dedup();
+                cells
+            }
+        }
+    }
+
+    /// Размер описывающего прямоугольника, включительно.
+    pub fn size(&self) -> (i32, i32) {
+        self.cells()
+            .iter()
+            .fold((0, 0), |(row, column), &(i, j)| (row.max(i), column.max(j)))
+    }
+
+    /// Клетка фигуры, ближайшая к центру описывающего прямоугольника.
+    pub fn center(&self) -> (i32, i32) {
+        let (row, column) = self.size();
+        let (ci, cj) = (row / 2, column / 2);
+        self.cells()
+            .into_iter()
+            .min_by_key(|&(i, j)| (i - ci).pow(2) + (j - cj).pow(2))
+            .unwrap_or((ci, cj))
+    }
+
+    /// Рисует фигуру полом комнаты, сдвинув ее на `(i, j)`.
+    pub fn apply_to_map<const ROW: usize, const COLUMN: usize>(
+        &self,
+        layer: &mut Layer<FloorType, ROW, COLUMN>,
+        (i, j): (i32, i32),
+    ) {
+        for (di, dj) in self.cells() {
+            layer[((i + di) as usize, (j + dj) as usize)] = FloorType::Room;
+        }
+    }
+}
+
+fn rectangle(row: i32, column: i32) -> impl Iterator<Item = (i32, i32)> {
+    (0..=row).flat_map(move |i| (0..=column).map(move |j| (i, j)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cells() {
+        let l_shape = RoomShape::LShape {
+            row: 3,
+            column: 3,
+            cut_row: 2,
+            cut_column: 2,
+            corner: CornerType::TopRight,
+        };
+        assert_eq!(l_shape.cells().len(), 16 - 4);
+        assert!(!l_shape.cells().contains(&(3, 3)));
+        assert!(l_shape.cells().contains(&(0, 3)));
+        assert_eq!(l_shape.size(), (3, 3));
+
+        let circle = RoomShape::Circle { radius: 2 };
+        assert_eq!(circle.cells().len(), 25 - 4);
+        assert_eq!(circle.center(), (2, 2));
+
+        let cross = RoomShape::cross(4, 4, 3);
+        assert_eq!(cross.cells().len(), 15 + 15 - 9);
+        assert!(!cross.cells().contains(&(0, 0)));
+        assert_eq!(cross.size(), (4, 4));
+    }
+
+    #[test]
+    fn test_center_inside_shape() {
+        let shapes = [
+            RoomShape::Rectangle { row: 4, column: 3 },
+            RoomShape::LShape {
+                row: 4,
+                column: 4,
+                cut_row: 3,
+                cut_column: 3,
+                corner: CornerType::BottomLeft,
+            },
+            RoomShape::Circle { radius: 2 },
+            RoomShape::cross(4, 4, 2),
+        ];
+        for shape in shapes.iter() {
+            assert!(shape.cells().contains(&shape.center()));
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use super::base::Layer;
+use super::room::Room;
+use crate::dungeon::enums::{FloorType, MarkerType, RoomRole};
+use rand::Rng;
+
+/// Вероятность, что подходящая комната получит шаблон.
+const TEMPLATE_CHANCE: f64 = 0.7;
+
+/// Точка внутри комнаты, отмеченная шаблоном для спауна.
+#[derive(Clone, Copy)]
+pub struct Marker {
+    pub i: i32,
+    pub j: i32,
+    pub marker_type: MarkerType,
+}
+
+/// Нарисованная вручную начинка комнаты.
+///
+/// `.` пол, `#` колонна или внутренняя стена, `~` яма,
+/// `E` точка спауна врага, `$` точка с добычей.
+/// Ямы не ставятся у краев: крайние клетки комнаты заняты ее стенами.
+pub struct RoomTemplate {
+    #[allow(dead_code)]
+    pub name: &'static str,
+    pub rows: &'static [&'static str],
+}
+
+pub const TEMPLATES: &[RoomTemplate] = &[
+    RoomTemplate {
+        name: "ambush",
+        rows: &["E..E", "....", "....", "E..E"],
+    },
+    RoomTemplate {
+        name: "pit_corners",
+        rows: &[".....", ".~.~.", ".....", ".~.~.", "....$"],
+    },
+    RoomTemplate {
+        name: "colonnade",
+        rows: &[".....", ".#.#.", ".#.#.", "E...E"],
+    },
+    RoomTemplate {
+        name: "sinkholes",
+        rows: &["E....", ".~.~.", ".....", "....$"],
+    },
+    RoomTemplate {
+        name: "pillars",
+        rows: &[".....", ".#.#.", "E...E", ".#.#.", "....."],
+    },
+    RoomTemplate {
+        name: "barricade",
+        rows: &[".....", ".###.", ".E...", ".....", "$...."],
+    },
+    RoomTemplate {
+        name: "cross",
+        rows: &["..E..", ".#.#.", "E...E", ".#.#.", "..$.."],
+    },
+];
+
+impl RoomTemplate {
+    /// Шаблон, отраженный по горизонтали (если `mirror`) и повернутый
+    /// на `rotation` четвертей оборота по часовой стрелке.
+    pub fn variant(&self, rotation: usize, mirror: bool) -> Vec<Vec<char>> {
+        let mut cells: Vec<Vec<char>> = self.rows.iter().map(|row| row.chars().collect()).collect();
+        if mirror {
+            for row in cells.iter_mut() {
+                row.reverse();
+            }
+        }
+        for _ in 0..rotation % 4 {
+            cells = rotate(&cells);
+        }
+        cells
+    }
+
+    pub fn variants(&self) -> impl Iterator<Item = Vec<Vec<char>>> + '_ {
+        (0..4).flat_map(move |rotation| {
+            [false, true]
+                .into_iter()
+                .map(move |mirror| self.variant(rotation, mirror))
+        })
+    }
+}
+
+fn rotate(cells: &[Vec<char>]) -> Vec<Vec<char>> {
+    let height = cells.len();
+    let width = cells.first().map_or(0, |row| row.len());
+    (0..width)
+        .map(|i| (0..height).map(|j| cells[height - 1 - j][i]).collect())
+        .collect()
+}
+
+fn fits(room: &Room, cells: &[Vec<char>]) -> bool {
+    room.shape.is_rectangle()
+        && cells.len() as i32 == room.row + 1
+        && cells.first().map_or(0, |row| row.len()) as i32 == room.column + 1
+}
+
+/// Переносит шаблон на пол комнаты и возвращает отмеченные в нем точки.
+pub fn apply_template_to_map<const ROW: usize, const COLUMN: usize>(
+    layer: &mut Layer<FloorType, ROW, COLUMN>,
+    room: &Room,
+    cells: &[Vec<char>],
+) -> Vec<Marker> {
+    let mut markers = Vec::new();
+    for (di, row) in cells.iter().enumerate() {
+        for (dj, cell) in row.iter().enumerate() {
+            let (i, j) = (room.i + di as i32, room.j + dj as i32);
+            let marker = |marker_type| Marker { i, j, marker_type };
+            match cell {
+                | '#' => layer[(i as usize, j as usize)] = FloorType::Empthy,
+                | '~' => layer[(i as usize, j as usize)] = FloorType::Pit,
+                | 'E' => markers.push(marker(MarkerType::Enemy)),
+                | '$' => markers.push(marker(MarkerType::Loot)),
+                | _ => {}
+            }
+        }
+    }
+    markers
+}
+
+/// Заполняет обычные комнаты и комнату босса шаблонами подходящего размера.
+/// Шаблон, который отрезал бы часть уровня, не применяется.
+pub fn apply_templates<const ROW: usize, const COLUMN: usize>(
+    rng: &mut impl Rng,
+    layer: &mut Layer<FloorType, ROW, COLUMN>,
+    rooms: &mut [Room],
+) {
+    for room in rooms
+        .iter_mut()
+        .filter(|room| matches!(room.role, RoomRole::Common | RoomRole::Boss))
+    {
+        if !rng.gen_bool(TEMPLATE_CHANCE) {
+            continue;
+        }
+        let variants: Vec<Vec<Vec<char>>> = TEMPLATES
+            .iter()
+            .flat_map(|template| template.variants())
+            .filter(|cells| fits(room, cells))
+            .collect();
+        if variants.is_empty() {
+            continue;
+        }
+
+        let cells = &variants[rng.gen_range(0..variants.len())];
+        let mut stamped = layer.clone();
+        let markers = apply_template_to_map(&mut stamped, room, cells);
+        if stamped.is_connected(|cell| stamped[cell].is_walkable()) {
+            *layer = stamped;
+            room.markers = markers;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates_keep_room_center() -> Result<(), String> {
+        for template in TEMPLATES.iter() {
+            for cells in template.variants() {
+                let room = Room::new(0, 0, cells.len() as i32 - 1, cells[0].len() as i32 - 1);
+                let (i, j) = room.center();
+                if cells.iter().any(|row| row.len() != cells[0].len()) {
+                    return Err(format!("template {} is not rectangular", template.name));
+                }
+                if cells[i as usize][j as usize] != '.' {
+                    return Err(format!("template {} covers the room center", template.name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pits_away_from_walls() {
+        for template in TEMPLATES.iter() {
+            let (height, width) = (template.rows.len(), template.rows[0].len());
+            for (i, row) in template.rows.iter().enumerate() {
+                for (j, cell) in row.chars().enumerate() {
+                    let border = i == 0 || j == 0 || i == height - 1 || j == width - 1;
+                    assert!(
+                        !(border && cell == '~'),
+                        "template {} has a pit at its edge",
+                        template.name
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotation() {
+        let template = RoomTemplate {
+            name: "test",
+            rows: &["#..", "..E"],
+        };
+        assert_eq!(
+            template.variant(1, false),
+            vec![vec!['.', '#'], vec!['.', '.'], vec!['E', '.']]
+        );
+        assert_eq!(
+            template.variant(0, true),
+            vec![vec!['.', '.', '#'], vec!['E', '.', '.']]
+        );
+    }
+}
@@ -2,15 +2,25 @@ use crate::dungeon::enums::{CornerType, DoorType, FloorType, TileType, WallType}
 
 use super::base::Layer;
 use super::room::RoomLayer;
+use std::collections::HashMap;
 
 pub struct WallLayer<const ROW: usize, const COLUMN: usize> {
     pub layer: Layer<TileType, ROW, COLUMN>,
 }
 
 impl<const ROW: usize, const COLUMN: usize> WallLayer<ROW, COLUMN> {
-    pub fn new(scale: f32, wall_layer: RoomLayer<ROW, COLUMN>) -> WallLayer<ROW, COLUMN> {
+    pub fn new(scale: f32, mut wall_layer: RoomLayer<ROW, COLUMN>) -> WallLayer<ROW, COLUMN> {
         let mut layer = Layer::new(TileType::Empthy, scale);
 
+        // Яма - часть пола комнаты, стены вокруг нее не ставятся
+        for i in 0..ROW {
+            for j in 0..COLUMN {
+                if wall_layer.layer[(i, j)] == FloorType::Pit {
+                    wall_layer.layer[(i, j)] = FloorType::Room;
+                }
+            }
+        }
+
         for (i, j, el) in wall_layer.layer.windows_2x2() {
             match el {
                 | [[FloorType::Empthy, FloorType::Empthy], [FloorType::Empthy, FloorType::Room]] => {
@@ -32,6 +42,30 @@ impl<const ROW: usize, const COLUMN: usize> WallLayer<ROW, COLUMN> {
             }
         }
 
+        // Углы фигурных комнат, у которых пуста не диагональная клетка, а только две стороны
+        for i in 1..ROW - 1 {
+            for j in 1..COLUMN - 1 {
+                if wall_layer.layer[(i, j)] != FloorType::Room || layer[(i, j)] != TileType::Empthy
+                {
+                    continue;
+                }
+                let empty = |cell| wall_layer.layer[cell] == FloorType::Empthy;
+                let corner = match (
+                    empty((i - 1, j)),
+                    empty((i + 1, j)),
+                    empty((i, j - 1)),
+                    empty((i, j + 1)),
+                ) {
+                    | (true, false, true, false) => CornerType::BottomLeft,
+                    | (true, false, false, true) => CornerType::BottomRight,
+                    | (false, true, true, false) => CornerType::TopLeft,
+                    | (false, true, false, true) => CornerType::TopRight,
+                    | _ => continue,
+                };
+                layer[(i, j)] = TileType::Wall(WallType::InternalCorner(corner));
+            }
+        }
+
         for (i, j, el) in wall_layer.layer.windows_1x3() {
             match el {
                 | [[FloorType::Empthy, FloorType::Room, FloorType::Room]] => {
@@ -64,42 +98,172 @@ impl<const ROW: usize, const COLUMN: usize> WallLayer<ROW, COLUMN> {
             }
         }
 
-        for (i, j, el) in wall_layer.layer.clone().windows_2x1() {
+        // Коридор соединяется с комнатой одной дверью, остальные
+        // клетки комнаты вдоль коридора отгорожены от него стеной
+        let doors = doors(&wall_layer);
+        let mut separate = |room, path| {
+            if doors.contains(&(room, path)) {
+                layer[room] = TileType::Door(door_type(room, path));
+            } else if layer[room] == TileType::Empthy {
+                layer[room] = TileType::Wall(wall_type(room, path));
+            }
+        };
+
+        for (i, j, el) in wall_layer.layer.windows_2x1() {
             match el {
-                | [[FloorType::Room], [FloorType::Path]] => {
-                    layer[(i, j)] = TileType::Door(DoorType::Right);
-                }
-                | [[FloorType::Path], [FloorType::Room]] => {
-                    layer[(i + 1, j)] = TileType::Door(DoorType::Left);
-                }
-                | [[FloorType::Empthy], [_]] => {
-                    //layer[(i, j)] = TileType::Wall(WallType::Bottom)
-                }
-                | [[_], [FloorType::Empthy]] => {
-                    //layer[(i + 1, j)] = TileType::Wall(WallType::Top)
-                }
+                | [[FloorType::Room], [FloorType::Path]] => separate((i, j), (i + 1, j)),
+                | [[FloorType::Path], [FloorType::Room]] => separate((i + 1, j), (i, j)),
                 | _ => {}
             }
         }
 
-        for (i, j, el) in wall_layer.layer.clone().windows_1x2() {
+        for (i, j, el) in wall_layer.layer.windows_1x2() {
             match el {
-                | [[FloorType::Room, FloorType::Path]] => {
-                    layer[(i, j)] = TileType::Door(DoorType::Bottom)
+                | [[FloorType::Room, FloorType::Path]] => separate((i, j), (i, j + 1)),
+                | [[FloorType::Path, FloorType::Room]] => separate((i, j + 1), (i, j)),
+                | _ => {}
+            }
+        }
+
+        WallLayer { layer }
+    }
+}
+
+type Cell = (usize, usize);
+
+/// Пары из клетки комнаты и клетки коридора, между которыми стоят двери:
+/// по одной на каждую связную часть коридора, примыкающую к комнате.
+/// Дверь ставится там, где коридор упирается в комнату, а не идет вдоль нее.
+fn doors<const ROW: usize, const COLUMN: usize>(
+    room_layer: &RoomLayer<ROW, COLUMN>,
+) -> Vec<(Cell, Cell)> {
+    let floor = &room_layer.layer;
+    let mut owners = Layer::<Option<usize>, ROW, COLUMN>::new(None, floor.scale);
+    for (index, room) in room_layer.rooms.iter().enumerate() {
+        for (i, j) in room.cells() {
+            owners[(i as usize, j as usize)] = Some(index);
+        }
+    }
+    let corridors = floor.components(|cell| floor[cell] == FloorType::Path);
+
+    let mut doors: HashMap<_, (Cell, Cell, bool)> = HashMap::new();
+    for i in 0..ROW {
+        for j in 0..COLUMN {
+            if floor[(i, j)] != FloorType::Room {
+                continue;
+            }
+            for path in floor.neighbours(i, j) {
+                let Some(corridor) = corridors[path] else {
+                    continue;
+                };
+                // Клетка за коридором на той же прямой: коридор ведет прямо в комнату
+                let beyond = ((2 * path.0).wrapping_sub(i), (2 * path.1).wrapping_sub(j));
+                let straight =
+                    beyond.0 < ROW && beyond.1 < COLUMN && floor[beyond] == FloorType::Path;
+                let room = owners[(i, j)].ok_or((i, j));
+                let door = doors
+                    .entry((corridor, room))
+                    .or_insert(((i, j), path, straight));
+                if straight && !door.2 {
+                    *door = ((i, j), path, straight);
                 }
-                | [[FloorType::Path, FloorType::Room]] => {
-                    layer[(i, j + 1)] = TileType::Door(DoorType::Top)
+            }
+        }
+    }
+    doors
+        .into_values()
+        .map(|(room, path, _)| (room, path))
+        .collect()
+}
+
+/// Дверь в клетке комнаты `room`, ведущая в соседнюю клетку коридора `path`.
+fn door_type(room: Cell, path: Cell) -> DoorType {
+    match (path.0 > room.0, path.0 < room.0, path.1 > room.1) {
+        | (true, _, _) => DoorType::Right,
+        | (_, true, _) => DoorType::Left,
+        | (_, _, true) => DoorType::Bottom,
+        | _ => DoorType::Top,
+    }
+}
+
+/// Стена в клетке комнаты `room`, отделяющая ее от соседней клетки `path`.
+fn wall_type(room: Cell, path: Cell) -> WallType {
+    match (path.1 < room.1, path.1 > room.1, path.0 < room.0) {
+        | (true, _, _) => WallType::Left,
+        | (_, true, _) => WallType::Right,
+        | (_, _, true) => WallType::Bottom,
+        | _ => WallType::Top,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::enums::{CorridorStyle, PropType};
+    use crate::dungeon::level::layer::room::{apply_room_to_map, Room};
+    use crate::dungeon::level::pathfinding::NavGrid;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_corridor_styles() -> Result<(), String> {
+        let styles = [
+            CorridorStyle::LShaped,
+            CorridorStyle::Wide,
+            CorridorStyle::Staircase,
+            CorridorStyle::Routed,
+        ];
+        for style in styles {
+            for _ in 0..20 {
+                let room_layer = RoomLayer::<15, 15>::new(&mut rand::thread_rng(), 4., 6, style);
+                let wall_layer = WallLayer::new(4., room_layer.clone());
+                let floor = &room_layer.layer;
+
+                if !floor.is_connected(|cell| floor[cell].is_walkable()) {
+                    return Err("corridors left a room unreachable".to_string());
                 }
-                | [[FloorType::Room | FloorType::Path, FloorType::Empthy]] => {
-                    //layer[(i, j)] = TileType::Wall(WallType::Right)
+                // Сквозь двери из любой клетки пола можно дойти до любой другой
+                let props = Layer::new(PropType::Empthy, 4.);
+                let grid = NavGrid::new(floor, &wall_layer.layer, &props);
+                let cells: Vec<_> = (0..15)
+                    .flat_map(|i| (0..15).map(move |j| (i, j)))
+                    .filter(|&cell| grid.is_walkable(cell))
+                    .collect();
+                let mut reached = HashSet::from([cells[0]]);
+                let mut queue = vec![cells[0]];
+                while let Some(cell) = queue.pop() {
+                    queue.extend(grid.steps(cell).filter(|&step| reached.insert(step)));
                 }
-                | [[FloorType::Empthy, FloorType::Room | FloorType::Path]] => {
-                    //layer[(i, j)] = TileType::Wall(WallType::Left)
+                if reached.len() != cells.len() {
+                    return Err("walls left a room unreachable".to_string());
                 }
-                | _ => {}
             }
         }
+        Ok(())
+    }
 
-        WallLayer { layer }
+    #[test]
+    fn test_pits_leave_walls_intact() {
+        let room = Room::new(1, 1, 4, 4);
+        let mut layer = Layer::<FloorType, 7, 7>::new(FloorType::Empthy, 4.);
+        apply_room_to_map(&mut layer, &room);
+        let room_layer = RoomLayer {
+            layer,
+            rooms: vec![room],
+        };
+        let mut pits = room_layer.clone();
+        pits.layer[(2, 2)] = FloorType::Pit;
+        pits.layer[(4, 3)] = FloorType::Pit;
+
+        let walls = WallLayer::new(4., room_layer).layer;
+        let with_pits = WallLayer::new(4., pits).layer;
+        for i in 0..7 {
+            for j in 0..7 {
+                assert!(
+                    with_pits[(i, j)] == walls[(i, j)],
+                    "wall differs at {:?}",
+                    (i, j)
+                );
+            }
+        }
     }
 }
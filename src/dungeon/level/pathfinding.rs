@@ -0,0 +1,326 @@
+//! Поиск пути по клеткам уровня: A* для одиночных запросов
+//! и карты Дейкстры для толпы, бегущей к одной цели.
+
+use super::layer::base::Layer;
+use super::visibility::{field_of_view, line, line_of_sight};
+use super::Level;
+use crate::dungeon::enums::{FloorType, PropType, TileType};
+use bevy::prelude::{Resource, Vec3};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+/// Ищет самый дешевый путь от `start` до `goal` по четырем направлениям.
+///
+/// `cost` получает клетку, из которой делается шаг, и клетку, в которую он
+/// делается, и возвращает цену шага или `None`, если шагнуть нельзя.
+/// Путь включает обе конечные клетки.
+pub fn a_star<T, const ROW: usize, const COLUMN: usize>(
+    layer: &Layer<T, ROW, COLUMN>,
+    start: (usize, usize),
+    goal: (usize, usize),
+    cost: impl Fn((usize, usize), (usize, usize)) -> Option<u32>,
+) -> Option<Vec<(usize, usize)>> {
+    let heuristic = |(i, j): (usize, usize)| (i.abs_diff(goal.0) + j.abs_diff(goal.1)) as u32;
+
+    let mut scores = [[u32::MAX; COLUMN]; ROW];
+    let mut came_from = [[None; COLUMN]; ROW];
+    let mut queue = BinaryHeap::from([Reverse((heuristic(start), 0, start))]);
+    scores[start.0][start.1] = 0;
+
+    while let Some(Reverse((_, score, cell))) = queue.pop() {
+        if cell == goal {
+            let mut path = vec![goal];
+            let mut current = goal;
+            while let Some(previous) = came_from[current.0][current.1] {
+                path.push(previous);
+                current = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if score > scores[cell.0][cell.1] {
+            continue;
+        }
+
+        for neighbour in layer.neighbours(cell.0, cell.1) {
+            let Some(step) = cost(cell, neighbour) else {
+                continue;
+            };
+            let new_score = score + step;
+            if new_score < scores[neighbour.0][neighbour.1] {
+                scores[neighbour.0][neighbour.1] = new_score;
+                came_from[neighbour.0][neighbour.1] = Some(cell);
+                queue.push(Reverse((
+                    new_score + heuristic(neighbour),
+                    new_score,
+                    neighbour,
+                )));
+            }
+        }
+    }
+
+    None
+}
+
+/// Карта проходимости уровня.
+///
+/// Клетка проходима, если это пол комнаты или коридора без твердого пропа.
+/// Комнату от коридора отделяет стена: из комнаты в коридор и обратно
+/// можно попасть и заглянуть только через клетку с дверью.
+#[derive(Resource, Clone)]
+pub struct NavGrid<const ROW: usize, const COLUMN: usize> {
+    floor: Layer<FloorType, ROW, COLUMN>,
+    doors: Layer<bool, ROW, COLUMN>,
+    blocked: Layer<bool, ROW, COLUMN>,
+}
+
+impl<const ROW: usize, const COLUMN: usize> NavGrid<ROW, COLUMN> {
+    pub fn new(
+        floor: &Layer<FloorType, ROW, COLUMN>,
+        tiles: &Layer<TileType, ROW, COLUMN>,
+        props: &Layer<PropType, ROW, COLUMN>,
+    ) -> NavGrid<ROW, COLUMN> {
+        let mut doors = Layer::new(false, floor.scale);
+        let mut blocked = Layer::new(false, floor.scale);
+        for i in 0..ROW {
+            for j in 0..COLUMN {
+                doors[(i, j)] = matches!(tiles[(i, j)], TileType::Door(_));
+                blocked[(i, j)] = props[(i, j)].is_solid();
+            }
+        }
+        NavGrid {
+            floor: floor.clone(),
+            doors,
+            blocked,
+        }
+    }
+
+    pub fn from_level(level: &Level<ROW, COLUMN>) -> NavGrid<ROW, COLUMN> {
+        NavGrid::new(
+            &level.room_layer.layer,
+            &level.wall_layer.layer,
+            &level.prop_layer.layer,
+        )
+    }
+
+    /// Клетка, над которой стоит точка мира.
+    pub fn cell(&self, position: Vec3) -> Option<(usize, usize)> {
+        self.floor.get_cell(position.x, position.z)
+    }
+
+    /// Центр клетки на уровне пола.
+    pub fn position(&self, (i, j): (usize, usize)) -> Vec3 {
+        let (x, z) = self.floor.get_coordiante(i, j);
+        Vec3::new(x, 0., z)
+    }
+
+    pub fn is_walkable(&self, cell: (usize, usize)) -> bool {
+        self.floor[cell].is_walkable() && !self.blocked[cell]
+    }
+
+    pub fn is_door(&self, cell: (usize, usize)) -> bool {
+        self.doors[cell]
+    }
+
+    /// Можно ли шагнуть из `from` в соседнюю клетку `to`.
+    pub fn can_step(&self, from: (usize, usize), to: (usize, usize)) -> bool {
+        self.is_walkable(from) && self.is_walkable(to) && !self.is_walled(from, to)
+    }
+
+    /// Стоит ли стена между соседними клетками: между комнатой
+    /// и коридором она есть везде, кроме дверей.
+    fn is_walled(&self, from: (usize, usize), to: (usize, usize)) -> bool {
+        match (self.floor[from], self.floor[to]) {
+            | (FloorType::Room, FloorType::Path) => !self.doors[from],
+            | (FloorType::Path, FloorType::Room) => !self.doors[to],
+            | _ => false,
+        }
+    }
+
+    /// Соседние клетки, в которые можно шагнуть из `cell`.
+    pub fn steps(&self, cell: (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.floor
+            .neighbours(cell.0, cell.1)
+            .filter(move |&neighbour| self.can_step(cell, neighbour))
+    }
+
+    /// Видна ли клетка `to` из `from`. Взгляд загораживают пустота за стенами
+    /// и стены между комнатами и коридорами, ямы и пропы видно насквозь.
+    pub fn is_visible(&self, from: (usize, usize), to: (usize, usize)) -> bool {
+        line_of_sight(from, to, |cell| self.is_opaque(cell)) && !self.is_walled_off(from, to)
+    }
+
+    /// Клетки, которые видно из `from` не дальше `radius` клеток.
+    pub fn field_of_view(&self, from: (usize, usize), radius: usize) -> Vec<(usize, usize)> {
+        field_of_view::<ROW, COLUMN>(from, radius, |cell| self.is_opaque(cell))
+            .into_iter()
+            .filter(|&cell| !self.is_walled_off(from, cell))
+            .collect()
+    }
+
+    /// Пересекает ли отрезок от `from` до `to` стену между комнатой и коридором.
+    fn is_walled_off(&self, from: (usize, usize), to: (usize, usize)) -> bool {
+        line(from, to)
+            .windows(2)
+            .any(|step| self.is_walled(step[0], step[1]))
+    }
+
+    fn is_opaque(&self, cell: (usize, usize)) -> bool {
+        self.floor[cell] == FloorType::Empthy
+    }
+
+    pub fn find_path(
+        &self,
+        start: (usize, usize),
+        goal: (usize, usize),
+    ) -> Option<Vec<(usize, usize)>> {
+        if !self.is_walkable(start) || !self.is_walkable(goal) {
+            return None;
+        }
+        a_star(&self.floor, start, goal, |from, to| {
+            self.can_step(from, to).then_some(1)
+        })
+    }
+}
+
+/// Карта Дейкстры: расстояние в шагах от каждой клетки до ближайшей цели.
+///
+/// Считается один раз на цель, после чего любой агент находит следующий
+/// шаг к цели за O(1), сколько бы их ни было.
+pub struct DijkstraMap<const ROW: usize, const COLUMN: usize> {
+    distances: Layer<u32, ROW, COLUMN>,
+}
+
+impl<const ROW: usize, const COLUMN: usize> DijkstraMap<ROW, COLUMN> {
+    pub fn new(
+        grid: &NavGrid<ROW, COLUMN>,
+        targets: &[(usize, usize)],
+    ) -> DijkstraMap<ROW, COLUMN> {
+        let mut distances = Layer::new(u32::MAX, grid.floor.scale);
+        let mut queue = VecDeque::new();
+        for &target in targets.iter().filter(|&&target| grid.is_walkable(target)) {
+            distances[target] = 0;
+            queue.push_back(target);
+        }
+
+        while let Some(cell) = queue.pop_front() {
+            for neighbour in grid.steps(cell) {
+                // Шаг считается в обратную сторону: агент идет из соседа в клетку
+                if grid.can_step(neighbour, cell) && distances[neighbour] == u32::MAX {
+                    distances[neighbour] = distances[cell] + 1;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        DijkstraMap { distances }
+    }
+
+    /// Число шагов до ближайшей цели или `None`, если цель недостижима.
+    pub fn distance(&self, cell: (usize, usize)) -> Option<u32> {
+        Some(self.distances[cell]).filter(|&distance| distance != u32::MAX)
+    }
+
+    /// Клетка, в которую надо шагнуть из `cell`, чтобы приблизиться к цели.
+    #[allow(dead_code)]
+    pub fn next_step(
+        &self,
+        grid: &NavGrid<ROW, COLUMN>,
+        cell: (usize, usize),
+    ) -> Option<(usize, usize)> {
+        let distance = self.distance(cell)?;
+        grid.steps(cell)
+            .filter(|&neighbour| self.distance(neighbour).is_some_and(|next| next < distance))
+            .min_by_key(|&neighbour| self.distances[neighbour])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::enums::DoorType;
+
+    /// `#` пустота, `.` пол комнаты, `=` коридор, `D` пол комнаты с дверью,
+    /// `o` пол комнаты с бочкой, `~` яма.
+    fn fixture<const ROW: usize, const COLUMN: usize>(rows: [&str; ROW]) -> NavGrid<ROW, COLUMN> {
+        let mut floor = Layer::new(FloorType::Empthy, 1.0);
+        let mut tiles = Layer::new(TileType::Empthy, 1.0);
+        let mut props = Layer::new(PropType::Empthy, 1.0);
+        for (i, row) in rows.iter().enumerate() {
+            for (j, cell) in row.chars().enumerate() {
+                floor[(i, j)] = match cell {
+                    | '.' | 'D' | 'o' => FloorType::Room,
+                    | '=' => FloorType::Path,
+                    | '~' => FloorType::Pit,
+                    | _ => FloorType::Empthy,
+                };
+                if cell == 'D' {
+                    tiles[(i, j)] = TileType::Door(DoorType::Right);
+                }
+                if cell == 'o' {
+                    props[(i, j)] = PropType::Barrel;
+                }
+            }
+        }
+        NavGrid::new(&floor, &tiles, &props)
+    }
+
+    #[test]
+    fn test_path_around_obstacles() {
+        let grid = fixture::<5, 5>([
+            ".....", //
+            ".###.", //
+            ".#~o.", //
+            ".#...", //
+            ".....",
+        ]);
+        let path = grid.find_path((2, 0), (2, 4)).unwrap();
+        assert_eq!(path.first(), Some(&(2, 0)));
+        assert_eq!(path.last(), Some(&(2, 4)));
+        assert_eq!(path.len(), 9);
+        assert!(grid.find_path((2, 0), (2, 2)).is_none());
+    }
+
+    #[test]
+    fn test_doors() {
+        let closed = fixture::<3, 5>([
+            "..###", //
+            "..===", //
+            "..###",
+        ]);
+        assert!(closed.find_path((1, 0), (1, 4)).is_none());
+
+        let open = fixture::<3, 5>([
+            "..###", //
+            ".D===", //
+            "..###",
+        ]);
+        assert_eq!(
+            open.find_path((1, 0), (1, 4)).map(|path| path.len()),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_dijkstra_map() {
+        let grid = fixture::<4, 6>([
+            "......", //
+            ".####.", //
+            ".#..#.", //
+            "....#.",
+        ]);
+        let map = DijkstraMap::new(&grid, &[(3, 5), (2, 2)]);
+        assert_eq!(map.distance((3, 5)), Some(0));
+        assert_eq!(map.distance((0, 0)), Some(6));
+        assert_eq!(map.distance((0, 5)), Some(3));
+        assert_eq!(map.distance((1, 1)), None);
+
+        let mut cell = (0, 4);
+        let mut steps = 0;
+        while let Some(next) = map.next_step(&grid, cell) {
+            cell = next;
+            steps += 1;
+        }
+        assert_eq!((cell, steps), ((3, 5), 4));
+    }
+}
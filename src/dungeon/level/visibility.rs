@@ -0,0 +1,286 @@
+//! Видимость клеток уровня.
+
+/// Клетки отрезка между центрами `from` и `to` по алгоритму Брезенхема, включая концы.
+pub fn line((i1, j1): (usize, usize), (i2, j2): (usize, usize)) -> Vec<(usize, usize)> {
+    let (mut i, mut j) = (i1 as i32, j1 as i32);
+    let (i2, j2) = (i2 as i32, j2 as i32);
+    let (di, dj) = ((i2 - i).abs(), -(j2 - j).abs());
+    let (step_i, step_j) = ((i2 - i).signum(), (j2 - j).signum());
+    let mut error = di + dj;
+
+    let mut cells = vec![(i as usize, j as usize)];
+    while (i, j) != (i2, j2) {
+        let doubled = 2 * error;
+        if doubled >= dj {
+            error += dj;
+            i += step_i;
+        }
+        if doubled <= di {
+            error += di;
+            j += step_j;
+        }
+        cells.push((i as usize, j as usize));
+    }
+    cells
+}
+
+/// Видна ли клетка `to` из `from`: ни одна клетка между ними не `opaque`.
+/// Сами концы отрезка могут быть непрозрачными.
+pub fn line_of_sight(
+    from: (usize, usize),
+    to: (usize, usize),
+    opaque: impl Fn((usize, usize)) -> bool,
+) -> bool {
+    let cells = line(from, to);
+    cells
+        .iter()
+        .skip(1)
+        .take(cells.len().saturating_sub(2))
+        .all(|&cell| !opaque(cell))
+}
+
+/// Множители, переводящие координаты первого октанта в каждый из восьми.
+const OCTANTS: [(i32, i32, i32, i32); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// Клетки, видимые из `origin` не дальше `radius`, найденные рекурсивным
+/// затенением по октантам. Непрозрачные клетки на границе видимости тоже
+/// попадают в результат: стену видно, но не то, что за ней.
+pub fn field_of_view<const ROW: usize, const COLUMN: usize>(
+    origin: (usize, usize),
+    radius: usize,
+    opaque: impl Fn((usize, usize)) -> bool,
+) -> Vec<(usize, usize)> {
+    let mut visible = [[false; COLUMN]; ROW];
+    visible[origin.0][origin.1] = true;
+    for octant in OCTANTS {
+        cast_light(
+            &mut visible,
+            &opaque,
+            origin,
+            radius as i32,
+            1,
+            (1.0, 0.0),
+            octant,
+        );
+    }
+
+    let mut cells = Vec::new();
+    for (i, row) in visible.iter().enumerate() {
+        for (j, &is_visible) in row.iter().enumerate() {
+            if is_visible {
+                cells.push((i, j));
+            }
+        }
+    }
+    cells
+}
+
+/// Освещает один октант начиная со строки `distance` между наклонами `slopes`
+/// и рекурсивно обходит тени, которые отбрасывают непрозрачные клетки.
+fn cast_light<const ROW: usize, const COLUMN: usize>(
+    visible: &mut [[bool; COLUMN]; ROW],
+    opaque: &impl Fn((usize, usize)) -> bool,
+    origin: (usize, usize),
+    radius: i32,
+    distance: i32,
+    (mut start, end): (f32, f32),
+    (xx, xy, yx, yy): (i32, i32, i32, i32),
+) {
+    if start < end {
+        return;
+    }
+    let mut next_start = start;
+    for row in distance..=radius {
+        let mut blocked = false;
+        for column in (0..=row).rev() {
+            let (dx, dy) = (-column, -row);
+            let left = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+            let right = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+            if start < right {
+                continue;
+            }
+            if end > left {
+                break;
+            }
+
+            let i = origin.0 as i32 + dx * xx + dy * xy;
+            let j = origin.1 as i32 + dx * yx + dy * yy;
+            let inside = i >= 0 && j >= 0 && (i as usize) < ROW && (j as usize) < COLUMN;
+            let cell = (i as usize, j as usize);
+            if inside && dx * dx + dy * dy <= radius * radius {
+                visible[cell.0][cell.1] = true;
+            }
+
+            let wall = !inside || opaque(cell);
+            if blocked {
+                if wall {
+                    next_start = right;
+                } else {
+                    blocked = false;
+                    start = next_start;
+                }
+            } else if wall && row < radius {
+                blocked = true;
+                cast_light(
+                    visible,
+                    opaque,
+                    origin,
+                    radius,
+                    row + 1,
+                    (start, left),
+                    (xx, xy, yx, yy),
+                );
+                next_start = right;
+            }
+        }
+        if blocked {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::enums::{FloorType, PropType};
+    use crate::dungeon::level::layer::base::Layer;
+    use crate::dungeon::level::layer::room::{
+        apply_column_tunnel, apply_room_to_map, apply_row_tunnel, Room,
+    };
+    use crate::dungeon::level::pathfinding::NavGrid;
+    use crate::dungeon::level::{Level, PropLayer, RoomLayer, WallLayer};
+
+    #[test]
+    fn test_line() {
+        assert_eq!(line((0, 0), (0, 3)), vec![(0, 0), (0, 1), (0, 2), (0, 3)]);
+        assert_eq!(line((2, 2), (0, 0)), vec![(2, 2), (1, 1), (0, 0)]);
+        let cells = line((0, 0), (4, 2));
+        assert_eq!(cells.len(), 5);
+        assert_eq!(cells.last(), Some(&(4, 2)));
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let wall = |cell| cell == (2, 2);
+        assert!(!line_of_sight((2, 0), (2, 4), wall));
+        assert!(line_of_sight((1, 0), (1, 4), wall));
+        assert!(line_of_sight((2, 0), (2, 2), wall));
+        assert!(line_of_sight((3, 3), (3, 3), wall));
+    }
+
+    /// Непрозрачные клетки карты, нарисованной символами: `#` — пустота
+    /// за стенами, все остальное — пол.
+    fn fixture<const ROW: usize, const COLUMN: usize>(
+        map: [&'static str; ROW],
+    ) -> impl Fn((usize, usize)) -> bool {
+        move |(i, j)| map[i].as_bytes()[j] == b'#'
+    }
+
+    #[test]
+    fn test_field_of_view_room() {
+        let opaque = fixture::<7, 7>([
+            "#######", "#.....#", "#.....#", "#..#..#", "#.....#", "#.....#", "#######",
+        ]);
+        let visible = field_of_view::<7, 7>((1, 3), 10, &opaque);
+        assert!(visible.contains(&(1, 3)));
+        assert!(
+            visible.contains(&(0, 3)),
+            "walls around the room are not visible"
+        );
+        assert!(visible.contains(&(3, 0)));
+        assert!(visible.contains(&(3, 3)));
+        assert!(!visible.contains(&(5, 3)), "visible behind the pillar");
+        assert!(visible.contains(&(5, 1)));
+
+        let near = field_of_view::<7, 7>((1, 1), 2, &opaque);
+        assert!(near.contains(&(2, 2)));
+        assert!(!near.contains(&(4, 4)), "visible beyond the radius");
+    }
+
+    #[test]
+    fn test_field_of_view_door_and_corner() {
+        let opaque = fixture::<9, 9>([
+            "#########",
+            "#...#####",
+            "#...#####",
+            "#........",
+            "#...#####",
+            "#...#.###",
+            "#...#.###",
+            "#.....###",
+            "#########",
+        ]);
+
+        // Сквозь дверь коридор просматривается по прямой, но не за стены
+        let visible = field_of_view::<9, 9>((3, 1), 10, &opaque);
+        assert!(visible.contains(&(3, 8)));
+        assert!(!visible.contains(&(5, 5)));
+        assert!(line_of_sight((3, 1), (3, 8), &opaque));
+        assert!(!line_of_sight((3, 1), (5, 5), &opaque));
+
+        // За угол Г-образного коридора не заглянуть
+        let visible = field_of_view::<9, 9>((7, 5), 10, &opaque);
+        assert!(visible.contains(&(5, 5)));
+        assert!(visible.contains(&(7, 1)));
+        assert!(!visible.contains(&(1, 1)));
+        assert!(!line_of_sight((5, 5), (3, 7), &opaque));
+    }
+
+    /// Уровень 7 на 10: комната 3 на 5 клеток, коридор входит в нее справа
+    /// и идет дальше вдоль ее нижней стороны, а двери там нет.
+    fn level() -> Level<7, 10> {
+        let mut layer = Layer::new(FloorType::Empthy, 1.);
+        let room = Room::new(1, 1, 2, 4);
+        apply_room_to_map(&mut layer, &room);
+        apply_column_tunnel(&mut layer, 2, 6, 7);
+        apply_row_tunnel(&mut layer, 2, 4, 7);
+        apply_column_tunnel(&mut layer, 4, 1, 7);
+        let room_layer = RoomLayer {
+            layer,
+            rooms: vec![room],
+        };
+        Level {
+            wall_layer: WallLayer::new(1., room_layer.clone()),
+            prop_layer: PropLayer {
+                layer: Layer::new(PropType::Empthy, 1.),
+                props: Vec::new(),
+            },
+            room_layer,
+        }
+    }
+
+    #[test]
+    fn test_field_of_view_wall_between_room_and_corridor() {
+        let level = level();
+        let grid = NavGrid::from_level(&level);
+        assert!(grid.is_door((2, 5)));
+        assert!(!grid.is_door((3, 2)));
+
+        // Коридор вдоль комнаты отгорожен стеной, видно его только сквозь дверь
+        let visible = grid.field_of_view((2, 2), 10);
+        assert!(visible.contains(&(3, 2)));
+        assert!(visible.contains(&(2, 7)));
+        for j in 1..6 {
+            assert!(
+                !visible.contains(&(4, j)),
+                "corridor visible through the wall"
+            );
+            assert!(!grid.is_visible((2, 2), (4, j)));
+        }
+        assert!(!grid.can_step((3, 2), (4, 2)));
+
+        // Из коридора в комнату тоже не заглянуть
+        let visible = grid.field_of_view((4, 2), 10);
+        assert!(visible.contains(&(4, 6)));
+        assert!(!visible.contains(&(3, 2)));
+    }
+}
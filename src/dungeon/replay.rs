@@ -0,0 +1,427 @@
+//! Запись и воспроизведение ввода. Запись хранит зерно уровня и то, что
+//! игроки просили у своих персонажей на каждом фиксированном шаге, так что
+//! при воспроизведении игроки проходят тот же путь при любой частоте кадров,
+//! с окном или без: враги, оружие и урон тоже живут на фиксированных шагах.
+//! Атаки входят в намерения, а взаимодействие, переключение камеры
+//! и переназначение управления записываются на ближайший следующий шаг.
+
+use crate::prelude::*;
+
+use crate::controls::ActionMap;
+use crate::dungeon::fog::FogOfWar;
+use crate::dungeon::level::LevelSettings;
+use crate::dungeon::save::{load_save, SaveFile};
+use bevy::app::AppExit;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayMode {
+    Off,
+    /// Записать игру в файл при выходе.
+    Record(PathBuf),
+    /// Проиграть запись из файла.
+    Play(PathBuf),
+}
+
+impl ReplayMode {
+    /// Режим из аргументов командной строки: `--record <файл>` или `--replay <файл>`.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                | "--record" => {
+                    return args
+                        .next()
+                        .map_or(ReplayMode::Off, |path| ReplayMode::Record(path.into()))
+                }
+                | "--replay" => {
+                    return args
+                        .next()
+                        .map_or(ReplayMode::Off, |path| ReplayMode::Play(path.into()))
+                }
+                | _ => {}
+            }
+        }
+        ReplayMode::Off
+    }
+}
+
+pub struct ReplayPlugin(pub ReplayMode);
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        match &self.0 {
+            | ReplayMode::Off => {}
+            | ReplayMode::Record(path) => {
+                app.insert_resource(Recorder {
+                    path: path.clone(),
+                    replay: Replay::default(),
+                    pending: Tick::default(),
+                })
+                .add_systems(PostStartup, start_recording)
+                .add_systems(PostUpdate, record_frame)
+                .add_systems(FixedUpdate, record_tick.before(ControllerStep))
+                .add_systems(Last, store_on_exit);
+            }
+            | ReplayMode::Play(path) => match Replay::load(path) {
+                | Ok(replay) => {
+                    app.insert_resource(Playback { replay, tick: 0 });
+                }
+                // Без записи игра идет как обычно
+                | Err(error) => warn!("could not load the replay: {}", error),
+            },
+        }
+        app.configure_sets(
+            Update,
+            PlayerInput.run_if(not(resource_exists::<Playback>())),
+        )
+        .add_systems(
+            PreStartup,
+            start_playback
+                .after(load_save)
+                .run_if(resource_exists::<Playback>()),
+        )
+        .add_systems(
+            FixedUpdate,
+            play_back
+                .before(ControllerStep)
+                .run_if(resource_exists::<Playback>()),
+        );
+    }
+}
+
+/// Записанная игра.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub depth: usize,
+    pub ticks: Vec<Tick>,
+}
+
+/// Один фиксированный шаг записи.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Tick {
+    /// Что персонажи просили на этом шаге, по номерам игроков.
+    pub intents: Vec<(usize, MovementIntent)>,
+    /// Действия, которые обрабатывает не контроллер, а подземелье,
+    /// по номерам игроков, в том порядке, в каком они были отправлены.
+    pub actions: Vec<(usize, MovementKind)>,
+    pub inputs: Vec<RecordedInput>,
+}
+
+/// Ввод, который не относится к персонажам.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum RecordedInput {
+    CycleCamera,
+    /// Управление после переназначения.
+    Rebind(ActionMap),
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        ron::from_str(&source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn store(&self, path: &Path) -> Result<(), String> {
+        let source = ron::ser::to_string_pretty(self, PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+        std::fs::write(path, source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+
+/// Запись, которая идет сейчас.
+#[derive(Resource)]
+pub struct Recorder {
+    pub path: PathBuf,
+    pub replay: Replay,
+    /// Действия и ввод кадров, которые еще не попали ни на один шаг.
+    pending: Tick,
+}
+
+/// Воспроизведение, которое идет сейчас. Пока оно есть, ввод игроков отключен.
+#[derive(Resource)]
+pub struct Playback {
+    pub replay: Replay,
+    /// Следующий шаг записи.
+    pub tick: usize,
+}
+
+fn start_recording(mut recorder: ResMut<Recorder>, settings: Option<Res<LevelSettings>>) {
+    if let Some(settings) = settings {
+        recorder.replay.seed = settings.seed;
+        recorder.replay.depth = settings.depth;
+    }
+}
+
+/// Откладывает до следующего шага то, что за кадр сделали игроки помимо движения.
+fn record_frame(
+    mut recorder: ResMut<Recorder>,
+    mut movement_event_reader: EventReader<MovementAction>,
+    mut cycle_event_reader: EventReader<CycleCameraMode>,
+    map: Option<Res<ActionMap>>,
+    players: Query<&PlayerId>,
+) {
+    let recorder = recorder.as_mut();
+    for event in movement_event_reader.read() {
+        let Ok(player) = players.get(event.entity) else {
+            continue;
+        };
+        if event.kind == MovementKind::Interact {
+            recorder.pending.actions.push((player.0, event.kind));
+        }
+    }
+    for _ in cycle_event_reader.read() {
+        recorder.pending.inputs.push(RecordedInput::CycleCamera);
+    }
+    if let Some(map) = map.filter(|map| map.is_changed() && !map.is_added()) {
+        recorder
+            .pending
+            .inputs
+            .push(RecordedInput::Rebind(map.clone()));
+    }
+}
+
+/// Записывает намерения персонажей игроков перед тем, как шаг их применит.
+fn record_tick(mut recorder: ResMut<Recorder>, players: Query<(&PlayerId, &MovementIntent)>) {
+    let mut tick = std::mem::take(&mut recorder.pending);
+    tick.intents = players
+        .iter()
+        .map(|(player, &intent)| (player.0, intent))
+        .collect();
+    tick.intents.sort_by_key(|&(player, _)| player);
+    recorder.replay.ticks.push(tick);
+}
+
+fn store_on_exit(mut exit_event_reader: EventReader<AppExit>, recorder: Res<Recorder>) {
+    if exit_event_reader.read().last().is_some() {
+        match recorder.replay.store(&recorder.path) {
+            | Ok(()) => info!("replay saved to {}", recorder.path.display()),
+            | Err(error) => warn!("could not save the replay: {}", error),
+        }
+    }
+}
+
+/// Строит уровень из зерна записи с неразведанным туманом и не дает
+/// воспроизведению затереть сохранение.
+fn start_playback(
+    mut commands: Commands,
+    playback: Res<Playback>,
+    settings: Option<ResMut<LevelSettings>>,
+) {
+    if let Some(mut settings) = settings {
+        settings.seed = playback.replay.seed;
+        settings.depth = playback.replay.depth;
+    }
+    commands.insert_resource(FogOfWar::default());
+    commands.remove_resource::<SaveFile>();
+}
+
+/// Подменяет намерения персонажей игроков записанными на этом шаге и отправляет
+/// остальные записанные действия. Когда запись кончается, управление
+/// возвращается игрокам.
+fn play_back(
+    mut commands: Commands,
+    mut playback: ResMut<Playback>,
+    mut movement_event_writer: EventWriter<MovementAction>,
+    mut cycle_event_writer: EventWriter<CycleCameraMode>,
+    mut players: Query<(Entity, &PlayerId, &mut MovementIntent)>,
+) {
+    let Some(tick) = playback.replay.ticks.get(playback.tick) else {
+        info!("the replay is over");
+        commands.remove_resource::<Playback>();
+        return;
+    };
+    for (_, player, mut intent) in &mut players {
+        if let Some(&(_, recorded)) = tick.intents.iter().find(|(id, _)| *id == player.0) {
+            *intent = recorded;
+        }
+    }
+    for &(id, kind) in tick.actions.iter() {
+        if let Some((entity, _, _)) = players.iter().find(|(_, player, _)| player.0 == id) {
+            movement_event_writer.send(MovementAction { entity, kind });
+        }
+    }
+    for input in tick.inputs.iter() {
+        match input {
+            | RecordedInput::CycleCamera => cycle_event_writer.send(CycleCameraMode),
+            | RecordedInput::Rebind(map) => commands.insert_resource(map.clone()),
+        }
+    }
+    playback.tick += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ai::{EnemyAi, EnemyAiPlugin, Steering};
+    use crate::dungeon::components::{Enemy, Player};
+    use crate::dungeon::{DUNGEON_COLUMN, DUNGEON_ROW};
+    use crate::testing::{self, Trajectory};
+    use bevy::input::keyboard::KeyboardInput;
+    use bevy::input::ButtonState;
+    use bevy_xpbd_3d::math::Vector;
+    use std::time::Duration;
+
+    const FRAMES: usize = 40;
+    /// Середина комнаты во весь уровень.
+    const START: Vec3 = Vec3::new(28.0, 1.0, 28.0);
+
+    /// Сколько раз переключалась камера.
+    #[derive(Resource, Default)]
+    struct Cycles(usize);
+
+    fn count_cycles(mut cycles: ResMut<Cycles>, mut events: EventReader<CycleCameraMode>) {
+        cycles.0 += events.read().count();
+    }
+
+    /// Игрок посреди комнаты, которым управляет клавиатура или запись,
+    /// и враг у него за спиной, с кадрами длиной `frame` миллисекунд.
+    fn app(mode: ReplayMode, frame: u64) -> App {
+        let mut app = testing::controller_app(Duration::from_millis(frame));
+        let room = (1..DUNGEON_ROW - 1).flat_map(|i| (1..DUNGEON_COLUMN - 1).map(move |j| (i, j)));
+        app.add_plugins((
+            ReplayPlugin(mode),
+            HealthPlugin,
+            CombatPlugin,
+            EnemyAiPlugin,
+        ))
+        .insert_resource(testing::arena(room, []))
+        .init_resource::<Cycles>()
+        .add_event::<CycleCameraMode>()
+        .add_systems(Update, count_cycles);
+        app.world.spawn((
+            RigidBody::Static,
+            Collider::cuboid(60.0, 1.0, 60.0),
+            TransformBundle::from_transform(Transform::from_xyz(START.x, -0.5, START.z)),
+        ));
+        let player = testing::spawn_player(&mut app, START);
+        app.world.entity_mut(player).insert((
+            Player,
+            Health::new(100.0),
+            MeleeAttack::new(1.0, 0.75),
+        ));
+
+        let mut weapon = MeleeAttack::new(5.0, 0.75);
+        weapon.size.z = 1.5;
+        let position = START + Vec3::Z * 1.2;
+        app.world.spawn((
+            Enemy,
+            EnemyAi::new(position, Vec::new()),
+            Steering::default(),
+            Health::new(100.0),
+            weapon,
+            CharacterControllerBundle::new(
+                Collider::capsule(0.25, 0.5),
+                Vector::NEG_Y * 9.81 * 2.0,
+            )
+            .with_movement(200.0, 0.1, 30.0, 0.1, 7.0, 0.5),
+            TransformBundle::from_transform(Transform::from_translation(position)),
+        ));
+        app
+    }
+
+    /// Клавиши, зажатые в кадре записи.
+    fn keys(frame: usize) -> Vec<KeyCode> {
+        match frame {
+            | 5..=20 => vec![KeyCode::W],
+            | 21..=24 | 26..=30 => vec![KeyCode::Q, KeyCode::D],
+            | 25 => vec![KeyCode::Q, KeyCode::D, KeyCode::F],
+            | 31 => vec![KeyCode::Space],
+            | _ => vec![],
+        }
+    }
+
+    /// Нажимает и отпускает клавиши событиями, чтобы нажатия были видны в кадре.
+    fn press(app: &mut App, frame: usize) {
+        let before = frame.checked_sub(1).map_or_else(Vec::new, keys);
+        let now = keys(frame);
+        let events = before
+            .iter()
+            .filter(|key| !now.contains(key))
+            .map(|&key| (key, ButtonState::Released))
+            .chain(
+                now.iter()
+                    .filter(|key| !before.contains(key))
+                    .map(|&key| (key, ButtonState::Pressed)),
+            );
+        for (key, state) in events.collect::<Vec<_>>() {
+            app.world.send_event(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key),
+                state,
+                window: Entity::PLACEHOLDER,
+            });
+        }
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let path = std::env::temp_dir().join("cult_of_eat_test_replay.ron");
+        let mut recording = app(ReplayMode::Record(path.clone()), 20);
+        for frame in 0..FRAMES {
+            press(&mut recording, frame);
+            if frame == 31 {
+                recording.world.send_event(CycleCameraMode);
+            }
+            recording.update();
+        }
+        recording.world.send_event(AppExit);
+        recording.update();
+        let recorded = std::mem::take(&mut recording.world.resource_mut::<Trajectory>().0);
+        let ticks = recording.world.resource::<Recorder>().replay.ticks.len();
+        assert_eq!(recorded.len(), ticks);
+        assert!(
+            recorded[ticks - 1].translation.xz().length() > 1.0,
+            "the player did not move"
+        );
+        assert_eq!(recording.world.resource::<Cycles>().0, 1);
+        let player_health = |app: &mut App| {
+            app.world
+                .query_filtered::<&Health, With<Player>>()
+                .single(&app.world)
+                .current
+        };
+        let recorded_health = player_health(&mut recording);
+        assert!(recorded_health < 100.0, "the enemy did not hit the player");
+
+        // Воспроизведение с другой частотой кадров проходит тот же путь по шагам
+        let mut replaying = app(ReplayMode::Play(path.clone()), 7);
+        // Туман другого уровня из сохранения не открывает клеток записи
+        replaying
+            .world
+            .insert_resource(FogOfWar::from_explored(&[(1, 1)]));
+        while replaying.world.contains_resource::<Playback>() {
+            replaying.update();
+        }
+        let replayed = &replaying.world.resource::<Trajectory>().0;
+        for (tick, expected) in recorded.iter().enumerate() {
+            assert_eq!(&replayed[tick], expected, "diverged at tick {}", tick);
+        }
+        assert_eq!(replaying.world.resource::<Cycles>().0, 1);
+        assert_eq!(player_health(&mut replaying), recorded_health);
+        assert!(replaying.world.resource::<FogOfWar>().explored().is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_replay() {
+        let path = std::env::temp_dir().join("cult_of_eat_test_missing_replay.ron");
+        let mut app = app(ReplayMode::Play(path), 20);
+        app.update();
+        assert!(!app.world.contains_resource::<Playback>());
+    }
+
+    #[test]
+    fn test_mode_from_args() {
+        let args = |args: &[&str]| ReplayMode::from_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&["game"]), ReplayMode::Off);
+        assert_eq!(
+            args(&["game", "--replay", "bug.ron"]),
+            ReplayMode::Play("bug.ron".into())
+        );
+        assert_eq!(args(&["game", "--record"]), ReplayMode::Off);
+    }
+}
@@ -0,0 +1,255 @@
+//! Сохранение игры: по зерну и глубине уровень строится заново таким же,
+//! а разведанные клетки снова открываются в тумане войны.
+
+use crate::prelude::*;
+
+use crate::dungeon::fog::FogOfWar;
+use crate::dungeon::level::LevelSettings;
+use bevy::app::AppExit;
+use bevy::asset::io::file::FileAssetReader;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Путь к сохранению относительно папки игры.
+const SAVE_PATH: &str = "save.ron";
+/// Как часто, в секундах, разведанное записывается на диск.
+const AUTOSAVE_INTERVAL: f32 = 5.0;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreStartup,
+            load_save
+                .run_if(resource_equals(StartMode::Continue))
+                .run_if(resource_exists::<SaveFile>()),
+        )
+        .add_systems(
+            Update,
+            (autosave, forget_on_death).run_if(resource_exists::<SaveFile>()),
+        )
+        .add_systems(Last, save_on_exit.run_if(resource_exists::<SaveFile>()));
+    }
+}
+
+/// Начинается ли новая игра или продолжается сохраненная.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartMode {
+    New,
+    /// Продолжить уровень из сохранения, если оно есть.
+    Continue,
+}
+
+impl StartMode {
+    /// Режим из аргументов командной строки: с `--continue` игра продолжается.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        if args.any(|arg| arg == "--continue") {
+            StartMode::Continue
+        } else {
+            StartMode::New
+        }
+    }
+}
+
+/// Файл, в который пишется сохранение. Без этого ресурса игра не сохраняется.
+#[derive(Resource)]
+pub struct SaveFile(pub PathBuf);
+
+impl Default for SaveFile {
+    fn default() -> Self {
+        SaveFile(FileAssetReader::get_base_path().join(SAVE_PATH))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SaveGame {
+    pub seed: u64,
+    pub depth: usize,
+    pub explored: Vec<(usize, usize)>,
+}
+
+impl SaveGame {
+    /// Читает сохранение. Отсутствующий или испорченный файл означает новую игру.
+    pub fn load(path: &Path) -> Option<Self> {
+        let source = std::fs::read_to_string(path).ok()?;
+        ron::from_str(&source)
+            .map_err(|error| warn!("{}: {}", path.display(), error))
+            .ok()
+    }
+
+    pub fn store(&self, path: &Path) -> Result<(), String> {
+        let source = ron::ser::to_string_pretty(self, PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+        std::fs::write(path, source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    fn capture(settings: &LevelSettings, fog: &FogOfWar) -> Self {
+        SaveGame {
+            seed: settings.seed,
+            depth: settings.depth,
+            explored: fog.explored(),
+        }
+    }
+}
+
+/// Продолжает сохраненную игру на том же уровне.
+pub fn load_save(
+    file: Res<SaveFile>,
+    mut settings: ResMut<LevelSettings>,
+    mut fog: ResMut<FogOfWar>,
+) {
+    if let Some(save) = SaveGame::load(&file.0) {
+        settings.seed = save.seed;
+        settings.depth = save.depth;
+        *fog = FogOfWar::from_explored(&save.explored);
+    }
+}
+
+fn store(file: &SaveFile, settings: &LevelSettings, fog: &FogOfWar) {
+    if let Err(error) = SaveGame::capture(settings, fog).store(&file.0) {
+        warn!("could not save the game: {}", error);
+    }
+}
+
+/// Записывает разведанное не чаще раза в [`AUTOSAVE_INTERVAL`] секунд.
+fn autosave(
+    time: Res<Time>,
+    file: Res<SaveFile>,
+    settings: Res<LevelSettings>,
+    fog: Res<FogOfWar>,
+    mut pending: Local<bool>,
+    mut since_save: Local<f32>,
+) {
+    *pending |= fog.is_changed();
+    *since_save += time.delta_seconds();
+    if *pending && *since_save >= AUTOSAVE_INTERVAL {
+        store(&file, &settings, &fog);
+        *pending = false;
+        *since_save = 0.0;
+    }
+}
+
+/// Когда погибает последний живой игрок, забег окончен: сохранение удаляется,
+/// и до конца игры она больше не сохраняется.
+fn forget_on_death(
+    mut commands: Commands,
+    mut died_event_reader: EventReader<Died>,
+    file: Res<SaveFile>,
+    players: Query<&Health, With<PlayerControlled>>,
+) {
+    let player_died = died_event_reader
+        .read()
+        .any(|event| players.contains(event.entity));
+    if !player_died || !players.iter().all(Health::is_dead) {
+        return;
+    }
+    match std::fs::remove_file(&file.0) {
+        | Err(error) if error.kind() != ErrorKind::NotFound => {
+            warn!("could not remove the save: {}", error);
+        }
+        | _ => {}
+    }
+    commands.remove_resource::<SaveFile>();
+}
+
+fn save_on_exit(
+    mut exit_event_reader: EventReader<AppExit>,
+    file: Res<SaveFile>,
+    settings: Res<LevelSettings>,
+    fog: Res<FogOfWar>,
+) {
+    if exit_event_reader.read().last().is_some() {
+        store(&file, &settings, &fog);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join("cult_of_eat_test_save.ron");
+        let save = SaveGame {
+            seed: 42,
+            depth: 2,
+            explored: vec![(1, 2), (3, 4)],
+        };
+        save.store(&path).unwrap();
+        assert_eq!(SaveGame::load(&path), Some(save));
+
+        std::fs::write(&path, "not a save").unwrap();
+        assert_eq!(SaveGame::load(&path), None);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(SaveGame::load(&path), None);
+    }
+
+    #[test]
+    fn test_start_mode_from_args() {
+        let args = |args: &[&str]| StartMode::from_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&["game"]), StartMode::New);
+        assert_eq!(args(&["game", "--continue"]), StartMode::Continue);
+    }
+
+    #[test]
+    fn test_forget_on_death() {
+        let path = std::env::temp_dir().join("cult_of_eat_test_forget.ron");
+        let mut app = testing::app();
+        app.add_plugins(HealthPlugin)
+            .insert_resource(SaveFile(path.clone()))
+            .insert_resource(LevelSettings::default())
+            .insert_resource(FogOfWar::default())
+            .add_systems(
+                Update,
+                forget_on_death.run_if(resource_exists::<SaveFile>()),
+            );
+        let save = SaveGame {
+            seed: 42,
+            depth: 2,
+            explored: Vec::new(),
+        };
+        save.store(&path).unwrap();
+        let first = app
+            .world
+            .spawn((
+                PlayerControlled,
+                Health::new(1.0),
+                OnDeath::Corpse,
+                GlobalTransform::default(),
+            ))
+            .id();
+        let second = app
+            .world
+            .spawn((
+                PlayerControlled,
+                Health::new(1.0),
+                OnDeath::Corpse,
+                GlobalTransform::default(),
+            ))
+            .id();
+        app.update();
+
+        // Пока кто-то из игроков жив, забег продолжается
+        app.world.send_event(Damage {
+            target: first,
+            amount: 1.0,
+        });
+        app.update();
+        app.update();
+        assert!(app.world.contains_resource::<SaveFile>());
+        assert!(path.exists());
+
+        app.world.send_event(Damage {
+            target: second,
+            amount: 1.0,
+        });
+        app.update();
+        app.update();
+        assert!(!app.world.contains_resource::<SaveFile>());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,262 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::{prelude::*, PhysicsSet};
+use serde::Deserialize;
+
+use crate::character::{CharacterController, ControllerGravity, ControllerStep, MovementBundle};
+
+/// Damage and deaths are handled on the fixed steps, after the controllers
+/// and the weapons that dealt the damage.
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Damage>().add_event::<Died>().add_systems(
+            FixedUpdate,
+            (tick_invulnerability, apply_damage, handle_death)
+                .chain()
+                .after(ControllerStep)
+                .before(PhysicsSet::Prepare),
+        );
+    }
+}
+
+/// The health of anything that can take damage and die.
+#[derive(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    /// The remaining health as a fraction from zero to one.
+    pub fn fraction(&self) -> f32 {
+        self.current / self.max
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+/// Invulnerability frames: after each hit the entity ignores damage for `duration` seconds.
+#[derive(Component)]
+pub struct Invulnerability {
+    pub duration: f32,
+    pub remaining: f32,
+}
+
+impl Invulnerability {
+    pub fn new(duration: f32) -> Self {
+        Self {
+            duration,
+            remaining: 0.0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.remaining > 0.0
+    }
+}
+
+/// What happens to the body of an entity once it dies.
+/// Entities without this component are despawned.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum OnDeath {
+    /// The entity disappears at once.
+    Despawn,
+    /// The body stays where it fell and stops moving.
+    Corpse,
+    /// The body stops being a character controller and tumbles as a dynamic rigid body.
+    Ragdoll,
+}
+
+/// A marker component for entities whose health ran out.
+#[derive(Component)]
+pub struct Dead;
+
+/// An event sent to hurt an entity with [`Health`].
+#[derive(Event)]
+pub struct Damage {
+    pub target: Entity,
+    pub amount: f32,
+}
+
+/// An event sent once when an entity's health runs out.
+#[derive(Event)]
+pub struct Died {
+    pub entity: Entity,
+    pub position: Vec3,
+}
+
+fn tick_invulnerability(time: Res<Time>, mut query: Query<&mut Invulnerability>) {
+    for mut invulnerability in &mut query {
+        invulnerability.remaining = (invulnerability.remaining - time.delta_seconds()).max(0.0);
+    }
+}
+
+/// Applies [`Damage`] events, starts invulnerability frames and reports deaths.
+pub fn apply_damage(
+    mut commands: Commands,
+    mut damage_event_reader: EventReader<Damage>,
+    mut died_event_writer: EventWriter<Died>,
+    mut targets: Query<
+        (&mut Health, &GlobalTransform, Option<&mut Invulnerability>),
+        Without<Dead>,
+    >,
+) {
+    for damage in damage_event_reader.read() {
+        let Ok((mut health, transform, invulnerability)) = targets.get_mut(damage.target) else {
+            continue;
+        };
+        if health.is_dead() {
+            continue;
+        }
+
+        if let Some(mut invulnerability) = invulnerability {
+            if invulnerability.is_active() {
+                continue;
+            }
+            invulnerability.remaining = invulnerability.duration;
+        }
+
+        health.current = (health.current - damage.amount).max(0.0);
+        if health.is_dead() {
+            commands.entity(damage.target).insert(Dead);
+            died_event_writer.send(Died {
+                entity: damage.target,
+                position: transform.translation(),
+            });
+        }
+    }
+}
+
+fn handle_death(
+    mut commands: Commands,
+    mut died_event_reader: EventReader<Died>,
+    mut bodies: Query<(Option<&OnDeath>, Option<&mut LinearVelocity>)>,
+) {
+    for event in died_event_reader.read() {
+        let Ok((on_death, velocity)) = bodies.get_mut(event.entity) else {
+            continue;
+        };
+
+        match on_death.copied().unwrap_or(OnDeath::Despawn) {
+            | OnDeath::Despawn => {
+                commands.entity(event.entity).despawn_recursive();
+            }
+            | OnDeath::Corpse => {
+                if let Some(mut velocity) = velocity {
+                    velocity.x = 0.0;
+                    velocity.z = 0.0;
+                }
+                commands.entity(event.entity).remove::<MovementBundle>();
+            }
+            | OnDeath::Ragdoll => {
+                commands
+                    .entity(event.entity)
+                    .remove::<(
+                        MovementBundle,
+                        CharacterController,
+                        ControllerGravity,
+                        ShapeCaster,
+                    )>()
+                    .insert((RigidBody::Dynamic, AngularVelocity(Vec3::X * 2.0)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn app() -> App {
+        let mut app = testing::app();
+        app.add_plugins(HealthPlugin);
+        app
+    }
+
+    fn hit(app: &mut App, target: Entity, amount: f32) {
+        app.world.send_event(Damage { target, amount });
+        app.update();
+    }
+
+    fn health(app: &App, entity: Entity) -> f32 {
+        app.world.get::<Health>(entity).unwrap().current
+    }
+
+    #[test]
+    fn test_invulnerability_frames() {
+        let mut app = app();
+        let target = app
+            .world
+            .spawn((
+                Health::new(10.0),
+                Invulnerability::new(0.5),
+                GlobalTransform::default(),
+            ))
+            .id();
+
+        app.world.send_event(Damage {
+            target,
+            amount: 3.0,
+        });
+        hit(&mut app, target, 3.0);
+        assert_eq!(
+            health(&app, target),
+            7.0,
+            "the second hit of the frame was not ignored"
+        );
+
+        hit(&mut app, target, 3.0);
+        assert_eq!(health(&app, target), 7.0);
+
+        for _ in 0..5 {
+            app.update();
+        }
+        hit(&mut app, target, 3.0);
+        assert_eq!(health(&app, target), 4.0);
+    }
+
+    #[test]
+    fn test_death() {
+        let mut app = app();
+        let despawned = app
+            .world
+            .spawn((Health::new(5.0), GlobalTransform::default()))
+            .id();
+        let corpse = app
+            .world
+            .spawn((
+                Health::new(5.0),
+                OnDeath::Corpse,
+                GlobalTransform::from_xyz(1.0, 0.0, 2.0),
+            ))
+            .id();
+        let mut died = app.world.resource::<Events<Died>>().get_reader();
+
+        for target in [despawned, corpse] {
+            app.world.send_event(Damage {
+                target,
+                amount: 8.0,
+            });
+        }
+        app.update();
+        hit(&mut app, corpse, 8.0);
+
+        let events = app.world.resource::<Events<Died>>();
+        let died: Vec<&Died> = died.read(events).collect();
+        assert_eq!(died.len(), 2, "died more than once");
+        assert_eq!(died[0].entity, despawned);
+        assert_eq!(died[1].position, Vec3::new(1.0, 0.0, 2.0));
+
+        assert!(app.world.get_entity(despawned).is_none());
+        assert!(app.world.get::<Dead>(corpse).is_some());
+        assert_eq!(health(&app, corpse), 0.0);
+    }
+}
@@ -1,17 +1,29 @@
+mod camera;
 mod character;
+mod combat;
+mod controls;
 mod dungeon;
+mod health;
 mod main_menu;
+#[cfg(test)]
+mod testing;
 mod prelude {
+    pub use super::camera::*;
     pub use super::character::*;
+    pub use super::combat::*;
+    pub use super::health::*;
     pub use bevy::ecs::system::Command;
     pub use bevy::prelude::*;
     pub use bevy_xpbd_3d::prelude::*;
-    pub use smooth_bevy_cameras::{LookTransform, LookTransformBundle, Smoother};
 }
 
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
+use camera::CameraPlugin;
 use character::CharacterControllerPlugin;
-use dungeon::DungeonPlugin;
+use combat::CombatPlugin;
+use controls::ControlsPlugin;
+use dungeon::{DungeonPlugin, ReplayMode, ReplayPlugin, StartMode};
+use health::HealthPlugin;
 use smooth_bevy_cameras::LookTransformPlugin;
 //use main_menu::MainMenuPlugin;
 use prelude::*;
@@ -27,10 +39,15 @@ fn main() {
         }))
         .add_plugins(LogDiagnosticsPlugin::default())
         .add_plugins(FrameTimeDiagnosticsPlugin::default())
-        .add_plugins(PhysicsPlugins::default())
+        .add_plugins(FixedPhysicsPlugin)
         .add_plugins(LookTransformPlugin)
+        .add_plugins(ControlsPlugin)
+        .add_plugins(CameraPlugin)
         .add_plugins(CharacterControllerPlugin)
-        .add_plugins(DungeonPlugin)
+        .add_plugins(HealthPlugin)
+        .add_plugins(CombatPlugin)
+        .add_plugins(DungeonPlugin(StartMode::from_args(std::env::args())))
+        .add_plugins(ReplayPlugin(ReplayMode::from_args(std::env::args())))
         //.add_plugins(MainMenuPlugin)
         .run();
 }
@@ -0,0 +1,96 @@
+//! Fixtures shared by the tests of several modules.
+
+use crate::controls::{ActionMap, CONTROLS_PATH};
+use crate::dungeon::enums::{FloorType, PropType, TileType};
+use crate::dungeon::level::layer::base::Layer;
+use crate::dungeon::DungeonNavGrid;
+use crate::prelude::*;
+use bevy::input::InputPlugin;
+use bevy::time::TimeUpdateStrategy;
+use bevy_xpbd_3d::math::Vector;
+use std::time::Duration;
+
+/// An app without rendering or windows whose every update, the first one
+/// included, advances the time by 100 ms.
+pub fn app() -> App {
+    let mut app = app_with_frame(Duration::from_millis(100));
+    // Otherwise the first update only starts the clock and runs no fixed steps
+    app.world
+        .resource_mut::<Time<Real>>()
+        .update_with_duration(Duration::ZERO);
+    app
+}
+
+/// Like [`app`], but every update advances the time by `frame`,
+/// except for the first one, which only starts the clock.
+pub fn app_with_frame(frame: Duration) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(frame));
+    app
+}
+
+/// The player's transform after each fixed step.
+#[derive(Resource, Default)]
+pub struct Trajectory(pub Vec<Transform>);
+
+fn track(mut trajectory: ResMut<Trajectory>, players: Query<&Transform, With<PlayerId>>) {
+    trajectory.0.extend(players.iter().copied());
+}
+
+/// Character controllers on the fixed step physics, driven by the shipped
+/// controls, that record the player's [`Trajectory`].
+pub fn controller_app(frame: Duration) -> App {
+    let mut app = app_with_frame(frame);
+    app.add_plugins((
+        TransformPlugin,
+        HierarchyPlugin,
+        InputPlugin,
+        FixedPhysicsPlugin,
+        CharacterControllerPlugin,
+    ))
+    .init_resource::<Assets<Mesh>>()
+    .init_resource::<SceneSpawner>()
+    .init_resource::<CameraSettings>()
+    .init_resource::<CursorAim>()
+    .init_resource::<Trajectory>()
+    .insert_resource(ActionMap::load(CONTROLS_PATH).unwrap())
+    .add_systems(FixedUpdate, track.after(PhysicsSet::Sync));
+    app
+}
+
+/// A keyboard controlled player with its center at `translation`.
+pub fn spawn_player(app: &mut App, translation: Vec3) -> Entity {
+    app.world
+        .spawn((
+            PlayerId(0),
+            PlayerControlled,
+            CharacterControllerBundle::new(
+                Collider::capsule(0.25, 0.5),
+                Vector::NEG_Y * 9.81 * 2.0,
+            )
+            .with_movement(500.0, 0.1, 30.0, 0.1, 7.0, 0.5),
+            TransformBundle::from_transform(Transform::from_translation(translation)),
+        ))
+        .id()
+}
+
+/// A hand drawn level with room floor at `rooms`, corridor floor at `paths`
+/// and nothing anywhere else, with 4 m cells.
+pub fn arena(
+    rooms: impl IntoIterator<Item = (usize, usize)>,
+    paths: impl IntoIterator<Item = (usize, usize)>,
+) -> DungeonNavGrid {
+    let mut floor = Layer::new(FloorType::Empthy, 4.0);
+    for cell in rooms {
+        floor[cell] = FloorType::Room;
+    }
+    for cell in paths {
+        floor[cell] = FloorType::Path;
+    }
+    DungeonNavGrid::new(
+        &floor,
+        &Layer::new(TileType::Empthy, 4.0),
+        &Layer::new(PropType::Empthy, 4.0),
+    )
+}